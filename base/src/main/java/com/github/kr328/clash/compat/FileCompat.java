@@ -1,6 +1,7 @@
 package com.github.kr328.clash.compat;
 
 import org.jetbrains.annotations.NotNull;
+import org.jetbrains.annotations.Nullable;
 
 import java.io.FileDescriptor;
 import java.io.FileInputStream;
@@ -11,8 +12,14 @@ import java.net.ProtocolFamily;
 import java.net.SocketAddress;
 import java.net.StandardProtocolFamily;
 import java.net.UnixDomainSocketAddress;
+import java.nio.channels.DatagramChannel;
+import java.nio.channels.Pipe.SinkChannel;
+import java.nio.channels.Pipe.SourceChannel;
+import java.nio.channels.ServerSocketChannel;
 import java.nio.channels.SocketChannel;
 import java.nio.channels.spi.SelectorProvider;
+import java.nio.file.Path;
+import java.time.Duration;
 
 public final class FileCompat {
     private static final Cleaner fdCleaner = Cleaner.create();
@@ -53,7 +60,7 @@ public final class FileCompat {
         nativeSetFileDescriptorInheritable(fd, inheritable);
     }
 
-    private static native void nativeCloseFileDescriptor(
+    static native void nativeCloseFileDescriptor(
             @NotNull final FileDescriptor fd
     );
 
@@ -114,6 +121,235 @@ public final class FileCompat {
         return new UnixSocketPair(firstChannel, secondChannel);
     }
 
+    private static native ServerSocketChannel nativeNewServerSocketChannel(
+            final SelectorProvider sp,
+            final FileDescriptor fd
+    ) throws IOException;
+
+    @NotNull
+    public static ServerSocketChannel newServerSocketChannel(@NotNull final FileDescriptor fd) throws IOException {
+        final ServerSocketChannel channel = nativeNewServerSocketChannel(SelectorProvider.provider(), fd);
+
+        fdCleaner.register(channel, () -> nativeCloseFileDescriptor(fd));
+
+        return channel;
+    }
+
+    private static native DatagramChannel nativeNewDatagramChannel(
+            final SelectorProvider sp,
+            final FileDescriptor fd
+    ) throws IOException;
+
+    @NotNull
+    public static DatagramChannel newDatagramChannel(@NotNull final FileDescriptor fd) throws IOException {
+        final DatagramChannel channel = nativeNewDatagramChannel(SelectorProvider.provider(), fd);
+
+        fdCleaner.register(channel, () -> nativeCloseFileDescriptor(fd));
+
+        return channel;
+    }
+
+    private static native SourceChannel nativeNewPipeSourceChannel(
+            final SelectorProvider sp,
+            final FileDescriptor fd
+    ) throws IOException;
+
+    @NotNull
+    public static SourceChannel newPipeSourceChannel(@NotNull final FileDescriptor fd) throws IOException {
+        final SourceChannel channel = nativeNewPipeSourceChannel(SelectorProvider.provider(), fd);
+
+        fdCleaner.register(channel, () -> nativeCloseFileDescriptor(fd));
+
+        return channel;
+    }
+
+    private static native SinkChannel nativeNewPipeSinkChannel(
+            final SelectorProvider sp,
+            final FileDescriptor fd
+    ) throws IOException;
+
+    @NotNull
+    public static SinkChannel newPipeSinkChannel(@NotNull final FileDescriptor fd) throws IOException {
+        final SinkChannel channel = nativeNewPipeSinkChannel(SelectorProvider.provider(), fd);
+
+        fdCleaner.register(channel, () -> nativeCloseFileDescriptor(fd));
+
+        return channel;
+    }
+
+    private static native void nativeCreateSealedFile(
+            @NotNull final String name,
+            final byte @NotNull [] content,
+            @NotNull final FileDescriptor fd
+    ) throws IOException;
+
+    public static @NotNull SealedFile createSealedFile(
+            @NotNull final String name,
+            final byte @NotNull [] content
+    ) throws IOException {
+        final FileDescriptor fd = new FileDescriptor();
+
+        nativeCreateSealedFile(name, content, fd);
+
+        final SealedFile file = new SealedFile(fd);
+
+        fdCleaner.register(file, () -> nativeCloseFileDescriptor(fd));
+
+        return file;
+    }
+
+    private static native boolean nativeLockFile(
+            @NotNull final FileDescriptor fd,
+            final boolean exclusive,
+            final long timeoutMillis
+    ) throws IOException;
+
+    private static native void nativeUnlockFile(@NotNull final FileDescriptor fd) throws IOException;
+
+    public static @NotNull FileLock lockFile(
+            @NotNull final FileDescriptor fd,
+            final boolean exclusive
+    ) throws IOException {
+        nativeLockFile(fd, exclusive, -1);
+
+        return new FileLock(fd);
+    }
+
+    public static @Nullable FileLock tryLockFile(
+            @NotNull final FileDescriptor fd,
+            final boolean exclusive
+    ) throws IOException {
+        return tryLockFile(fd, exclusive, Duration.ZERO);
+    }
+
+    public static @Nullable FileLock tryLockFile(
+            @NotNull final FileDescriptor fd,
+            final boolean exclusive,
+            @NotNull final Duration timeout
+    ) throws IOException {
+        if (nativeLockFile(fd, exclusive, Math.max(timeout.toMillis(), 0))) {
+            return new FileLock(fd);
+        }
+
+        return null;
+    }
+
+    private static native void nativeWriteFileAtomically(
+            @NotNull final String path,
+            final byte @NotNull [] content,
+            @Nullable final String backup
+    ) throws IOException;
+
+    public static void writeFileAtomically(
+            @NotNull final Path path,
+            final byte @NotNull [] content,
+            @Nullable final Path backup
+    ) throws IOException {
+        nativeWriteFileAtomically(
+                path.toAbsolutePath().toString(),
+                content,
+                backup != null ? backup.toAbsolutePath().toString() : null
+        );
+    }
+
+    private static native long nativeStartForward(
+            @NotNull final FileDescriptor source,
+            @NotNull final FileDescriptor target,
+            final int pipeCapacity,
+            @NotNull final ForwardListener listener
+    ) throws IOException;
+
+    private static native long nativeGetForwardTransferred(final long ptr);
+
+    private static native long nativeStopForward(final long ptr);
+
+    public static @NotNull Forwarder startForward(
+            @NotNull final FileDescriptor source,
+            @NotNull final FileDescriptor target,
+            final int pipeCapacity,
+            @NotNull final ForwardListener listener
+    ) throws IOException {
+        return new Forwarder(nativeStartForward(source, target, pipeCapacity, listener));
+    }
+
+    private static native long nativeCreatePrivateDirectory(@NotNull final String prefix) throws IOException;
+
+    private static native String nativeGetPrivateDirectoryPath(final long ptr);
+
+    private static native void nativeReleasePrivateDirectory(final long ptr);
+
+    public static @NotNull PrivateDirectory createPrivateDirectory(@NotNull final String prefix) throws IOException {
+        return new PrivateDirectory(nativeCreatePrivateDirectory(prefix));
+    }
+
+    @SuppressWarnings("unused")
+    public interface ForwardListener {
+        void onClosed(final long transferred, @Nullable final String error);
+    }
+
+    public static final class Forwarder implements AutoCloseable {
+        private final ForwarderState state;
+        private final Cleaner.Cleanable cleanable;
+
+        private Forwarder(final long ptr) {
+            this.state = new ForwarderState(ptr);
+            this.cleanable = fdCleaner.register(this, state);
+        }
+
+        public long getTransferred() {
+            return state.getTransferred();
+        }
+
+        @Override
+        public void close() {
+            cleanable.clean();
+        }
+    }
+
+    private static final class ForwarderState implements Runnable {
+        private final long ptr;
+
+        private boolean stopped;
+        private long transferred;
+
+        private ForwarderState(final long ptr) {
+            this.ptr = ptr;
+        }
+
+        private synchronized long getTransferred() {
+            if (stopped) {
+                return transferred;
+            }
+
+            return nativeGetForwardTransferred(ptr);
+        }
+
+        @Override
+        public synchronized void run() {
+            transferred = nativeStopForward(ptr);
+            stopped = true;
+        }
+    }
+
+    public static final class PrivateDirectory implements AutoCloseable {
+        private final Path path;
+        private final Cleaner.Cleanable cleanable;
+
+        private PrivateDirectory(final long ptr) {
+            this.path = Path.of(nativeGetPrivateDirectoryPath(ptr));
+            this.cleanable = fdCleaner.register(this, () -> nativeReleasePrivateDirectory(ptr));
+        }
+
+        public @NotNull Path getPath() {
+            return path;
+        }
+
+        @Override
+        public void close() {
+            cleanable.clean();
+        }
+    }
+
     public record Pipe(@NotNull FileInputStream reader, @NotNull FileOutputStream writer) implements AutoCloseable {
         @Override
         public void close() {
@@ -129,4 +365,18 @@ public final class FileCompat {
             Utils.closeSilent(second);
         }
     }
+
+    public record FileLock(@NotNull FileDescriptor fd) implements AutoCloseable {
+        @Override
+        public void close() throws IOException {
+            nativeUnlockFile(fd);
+        }
+    }
+
+    public record SealedFile(@NotNull FileDescriptor fd) implements AutoCloseable {
+        @Override
+        public void close() {
+            nativeCloseFileDescriptor(fd);
+        }
+    }
 }
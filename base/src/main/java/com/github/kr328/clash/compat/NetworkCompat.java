@@ -1,24 +1,47 @@
 package com.github.kr328.clash.compat;
 
 import org.jetbrains.annotations.NotNull;
+import org.jetbrains.annotations.Nullable;
 
+import java.io.FileDescriptor;
 import java.io.IOException;
+import java.lang.ref.Cleaner;
+import java.net.InetAddress;
+import java.net.InetSocketAddress;
+import java.util.ArrayList;
 import java.util.List;
+import java.util.Objects;
+import java.util.Set;
+import java.util.stream.Stream;
 
 public final class NetworkCompat {
+    public static final int CHANGE_LINK = 1;
+    public static final int CHANGE_ADDRESS = 1 << 1;
+    public static final int CHANGE_ROUTE = 1 << 2;
+
+    private static final int BIND_TCP = 1;
+    private static final int BIND_UDP = 1 << 1;
+    private static final int BIND_REUSE_ADDRESS = 1 << 2;
+
+    private static final Cleaner holderCleaner = Cleaner.create();
+    private static final Cleaner fdCleaner = Cleaner.create();
+
     static {
         CompatLibrary.load();
     }
 
     private static native boolean nativeIsSystemProxySupported();
 
+    // On linux sessions without GNOME or KDE only NetworkManager is available, which accepts auto config (PAC) proxies only
     public static boolean isSystemProxySupported() {
         return nativeIsSystemProxySupported();
     }
 
     private static native void nativeSetSystemProxy(
             final boolean enabled,
-            @NotNull final String address,
+            @NotNull final String @NotNull [] hosts,
+            final int @NotNull [] ports,
+            @Nullable final String autoConfigUrl,
             @NotNull final String @NotNull [] excludes
     ) throws IOException;
 
@@ -27,6 +50,605 @@ public final class NetworkCompat {
             @NotNull final String address,
             @NotNull final List<String> excludes
     ) throws IOException {
-        nativeSetSystemProxy(enabled, address, excludes.toArray(String[]::new));
+        if (!enabled) {
+            setSystemProxy(false, new SystemProxyConfig(null, null, null, null, null, List.of()));
+
+            return;
+        }
+
+        final int separator = address.lastIndexOf(':');
+        if (separator < 0) {
+            throw new IOException("Invalid proxy address: " + address);
+        }
+
+        final ProxyEndpoint endpoint;
+        try {
+            endpoint = new ProxyEndpoint(
+                    address.substring(0, separator),
+                    Integer.parseInt(address.substring(separator + 1))
+            );
+        } catch (final NumberFormatException e) {
+            throw new IOException("Invalid proxy address: " + address, e);
+        }
+
+        setSystemProxy(true, new SystemProxyConfig(endpoint, endpoint, null, null, null, excludes));
+    }
+
+    /**
+     * Applies the proxy to the desktop session and, on linux, to the primary NetworkManager connection as well.
+     * <p>
+     * The NetworkManager profile is only changed in memory: the profile on disk is left untouched, so the change is
+     * lost when NetworkManager restarts and NetworkManager shows the profile as unsaved until then. The device is
+     * reapplied to pick up the change, devices that refuse to reapply are activated again, which drops connectivity
+     * for a moment. Nothing is applied when the profile already holds the same proxy.
+     */
+    public static void setSystemProxy(
+            final boolean enabled,
+            @NotNull final SystemProxyConfig config
+    ) throws IOException {
+        nativeSetSystemProxy(
+                enabled,
+                config.hosts(),
+                config.ports(),
+                config.autoConfigUrl(),
+                config.excludes().toArray(String[]::new)
+        );
+    }
+
+    private static native void nativeSetProxyEnvironment(
+            final boolean enabled,
+            @NotNull final String appId,
+            @NotNull final String @NotNull [] hosts,
+            final int @NotNull [] ports,
+            @NotNull final String @NotNull [] excludes
+    ) throws IOException;
+
+    public static void setProxyEnvironment(
+            final boolean enabled,
+            @NotNull final String appId,
+            @NotNull final SystemProxyConfig config
+    ) throws IOException {
+        nativeSetProxyEnvironment(
+                enabled,
+                appId,
+                config.hosts(),
+                config.ports(),
+                config.excludes().toArray(String[]::new)
+        );
+    }
+
+    private static native NativeSystemProxy nativeGetSystemProxy() throws IOException;
+
+    private static @NotNull SystemProxy toSystemProxy(@NotNull final NativeSystemProxy proxy) {
+        final ProxyEndpoint[] endpoints = new ProxyEndpoint[proxy.hosts().length];
+        for (int i = 0; i < endpoints.length; i++) {
+            if (!proxy.hosts()[i].isEmpty()) {
+                endpoints[i] = new ProxyEndpoint(proxy.hosts()[i], proxy.ports()[i]);
+            }
+        }
+
+        return new SystemProxy(
+                ProxyMode.values()[proxy.mode()],
+                endpoints[0],
+                endpoints[1],
+                endpoints[2],
+                endpoints[3],
+                proxy.autoConfigUrl(),
+                List.of(proxy.excludes())
+        );
+    }
+
+    public static @NotNull SystemProxy getSystemProxy() throws IOException {
+        return toSystemProxy(nativeGetSystemProxy());
+    }
+
+    private static native NativeExcludeResult @NotNull [] nativeValidateExcludes(
+            @NotNull final String @NotNull [] excludes
+    );
+
+    public static @NotNull List<@NotNull ExcludeResult> validateExcludes(@NotNull final List<@NotNull String> excludes) {
+        final NativeExcludeResult[] results = nativeValidateExcludes(excludes.toArray(String[]::new));
+
+        final List<ExcludeResult> ret = new ArrayList<>();
+        for (int i = 0; i < results.length; i++) {
+            ret.add(new ExcludeResult(excludes.get(i), results[i].normalized(), results[i].error()));
+        }
+
+        return List.copyOf(ret);
+    }
+
+    private static native long nativeAddProxyChangedListener(
+            @NotNull final String @Nullable [] hosts,
+            final int @Nullable [] ports,
+            @Nullable final String autoConfigUrl,
+            @NotNull final String @Nullable [] excludes,
+            @NotNull final NativeProxyChangedListener listener
+    ) throws IOException;
+
+    private static native void nativeReleaseHolder(final long ptr);
+
+    @NotNull
+    public static Disposable addProxyChangedListener(@NotNull final OnProxyChangedListener listener) throws IOException {
+        return addProxyChangedListener(null, listener);
+    }
+
+    @NotNull
+    public static Disposable addProxyChangedListener(
+            @Nullable final SystemProxyConfig enforce,
+            @NotNull final OnProxyChangedListener listener
+    ) throws IOException {
+        final NativeProxyChangedListener nativeListener = proxy -> listener.onChanged(toSystemProxy(proxy));
+
+        if (enforce == null) {
+            return new Holder(nativeAddProxyChangedListener(null, null, null, null, nativeListener));
+        }
+
+        return new Holder(nativeAddProxyChangedListener(
+                enforce.hosts(),
+                enforce.ports(),
+                enforce.autoConfigUrl(),
+                enforce.excludes().toArray(String[]::new),
+                nativeListener
+        ));
+    }
+
+    private static native long nativeAddNetworkChangedListener(
+            @NotNull final OnNetworkChangedListener listener
+    ) throws IOException;
+
+    @NotNull
+    public static Disposable addNetworkChangedListener(@NotNull final OnNetworkChangedListener listener) throws IOException {
+        return new Holder(nativeAddNetworkChangedListener(listener));
+    }
+
+    private static native void nativeSetLinkDns(
+            final boolean enabled,
+            final int interfaceIndex,
+            @NotNull final String @NotNull [] servers
+    ) throws IOException;
+
+    public static void setLinkDns(
+            final boolean enabled,
+            final int interfaceIndex,
+            @NotNull final List<@NotNull InetAddress> servers
+    ) throws IOException {
+        nativeSetLinkDns(
+                enabled,
+                interfaceIndex,
+                servers.stream().map(NetworkCompat::formatAddress).toArray(String[]::new)
+        );
+    }
+
+    private static native NativePortOwner @NotNull [] nativeFindPortOwners(final boolean udp, final int port) throws IOException;
+
+    public static @NotNull List<@NotNull PortOwner> findPortOwners(
+            @NotNull final Protocol protocol,
+            final int port
+    ) throws IOException {
+        return Stream.of(nativeFindPortOwners(protocol == Protocol.UDP, port))
+                .map(o -> new PortOwner(o.pid(), o.executable(), List.of(o.commandLine())))
+                .toList();
+    }
+
+    private static native NativeBindResult @NotNull [] nativeProbeBind(
+            @NotNull final String @NotNull [] hosts,
+            final int @NotNull [] ports,
+            final int @NotNull [] flags,
+            final int rangeStart,
+            final int rangeEnd
+    ) throws IOException;
+
+    public static @NotNull List<@NotNull BindResult> probeBind(
+            @NotNull final List<@NotNull BindRequest> requests,
+            final int rangeStart,
+            final int rangeEnd
+    ) throws IOException {
+        final String[] hosts = new String[requests.size()];
+        final int[] ports = new int[requests.size()];
+        final int[] flags = new int[requests.size()];
+
+        for (int i = 0; i < requests.size(); i++) {
+            final BindRequest request = requests.get(i);
+            if (request.address().isUnresolved()) {
+                throw new IOException("Unresolved address: " + request.address());
+            }
+
+            hosts[i] = formatAddress(request.address().getAddress());
+            ports[i] = request.address().getPort();
+            flags[i] = (request.protocols().contains(Protocol.TCP) ? BIND_TCP : 0)
+                    | (request.protocols().contains(Protocol.UDP) ? BIND_UDP : 0)
+                    | (request.reuseAddress() ? BIND_REUSE_ADDRESS : 0);
+        }
+
+        final NativeBindResult[] results = nativeProbeBind(hosts, ports, flags, rangeStart, rangeEnd);
+
+        final List<BindResult> ret = new ArrayList<>();
+        for (int i = 0; i < results.length; i++) {
+            ret.add(new BindResult(requests.get(i), results[i].error(), results[i].suggestedPort()));
+        }
+
+        return List.copyOf(ret);
+    }
+
+    private static native String nativeCreateTun(
+            @NotNull final String name,
+            final int mtu,
+            @NotNull final String @NotNull [] addresses,
+            @NotNull final FileDescriptor @NotNull [] fds
+    ) throws IOException;
+
+    @NotNull
+    public static TunDevice createTun(
+            @Nullable final String name,
+            final int mtu,
+            @NotNull final List<@NotNull String> addresses,
+            final int queues
+    ) throws IOException {
+        final FileDescriptor[] fds = Stream.generate(FileDescriptor::new)
+                .limit(Math.max(queues, 1))
+                .toArray(FileDescriptor[]::new);
+
+        final String actualName = nativeCreateTun(
+                Objects.requireNonNullElse(name, ""),
+                mtu,
+                addresses.toArray(String[]::new),
+                fds
+        );
+
+        return new TunDevice(actualName, List.of(fds));
+    }
+
+    private static @NotNull InterfaceAddress parseInterfaceAddress(@NotNull final String address) throws IOException {
+        final int separator = address.lastIndexOf('/');
+
+        return new InterfaceAddress(
+                InetAddress.getByName(address.substring(0, separator)),
+                Integer.parseInt(address.substring(separator + 1))
+        );
+    }
+
+    private static native NativeInterface @NotNull [] nativeListInterfaces() throws IOException;
+
+    public static @NotNull List<@NotNull Interface> listInterfaces() throws IOException {
+        final List<Interface> interfaces = new ArrayList<>();
+
+        for (final NativeInterface iface : nativeListInterfaces()) {
+            final List<InterfaceAddress> addresses = new ArrayList<>();
+            for (final String address : iface.addresses()) {
+                addresses.add(parseInterfaceAddress(address));
+            }
+
+            interfaces.add(new Interface(
+                    iface.name(),
+                    iface.index(),
+                    iface.flags(),
+                    iface.type(),
+                    iface.kind(),
+                    OperState.values()[iface.operState()],
+                    iface.mtu(),
+                    iface.hardwareAddress(),
+                    List.copyOf(addresses)
+            ));
+        }
+
+        return List.copyOf(interfaces);
+    }
+
+    private static native NativeRoute @NotNull [] nativeGetDefaultRoutes() throws IOException;
+
+    private static @NotNull List<@NotNull Route> toRoutes(@NotNull final NativeRoute @NotNull [] nativeRoutes) throws IOException {
+        final List<Route> routes = new ArrayList<>();
+
+        for (final NativeRoute route : nativeRoutes) {
+            routes.add(new Route(
+                    parseInterfaceAddress(route.destination()),
+                    route.gateway() != null ? InetAddress.getByName(route.gateway()) : null,
+                    route.interfaceIndex(),
+                    route.interfaceName(),
+                    route.metric(),
+                    route.table()
+            ));
+        }
+
+        return List.copyOf(routes);
+    }
+
+    public static @NotNull List<@NotNull Route> getDefaultRoutes() throws IOException {
+        return toRoutes(nativeGetDefaultRoutes());
+    }
+
+    private static @NotNull String formatAddress(@NotNull final InetAddress address) {
+        final String host = address.getHostAddress();
+        final int scope = host.indexOf('%');
+
+        return scope < 0 ? host : host.substring(0, scope);
+    }
+
+    private static native void nativeAddRoute(
+            @NotNull final String destination,
+            @Nullable final String gateway,
+            @NotNull final String interfaceName,
+            final int metric,
+            final int table
+    ) throws IOException;
+
+    private static native void nativeDeleteRoute(
+            @NotNull final String destination,
+            @Nullable final String gateway,
+            @NotNull final String interfaceName,
+            final int metric,
+            final int table
+    ) throws IOException;
+
+    private static native NativeRoute @NotNull [] nativeListInstalledRoutes() throws IOException;
+
+    public static void addRoute(@NotNull final RouteConfig route) throws IOException {
+        nativeAddRoute(
+                formatAddress(route.destination().address()) + "/" + route.destination().prefixLength(),
+                route.gateway() != null ? formatAddress(route.gateway()) : null,
+                route.interfaceName(),
+                route.metric(),
+                route.table()
+        );
+    }
+
+    public static void deleteRoute(@NotNull final RouteConfig route) throws IOException {
+        nativeDeleteRoute(
+                formatAddress(route.destination().address()) + "/" + route.destination().prefixLength(),
+                route.gateway() != null ? formatAddress(route.gateway()) : null,
+                route.interfaceName(),
+                route.metric(),
+                route.table()
+        );
+    }
+
+    public static @NotNull List<@NotNull Route> listInstalledRoutes() throws IOException {
+        return toRoutes(nativeListInstalledRoutes());
+    }
+
+    private static native void nativeAddRule(
+            final boolean ipv6,
+            final int fwmark,
+            final int priority,
+            final int table
+    ) throws IOException;
+
+    private static native void nativeDeleteRule(
+            final boolean ipv6,
+            final int fwmark,
+            final int priority,
+            final int table
+    ) throws IOException;
+
+    private static native Rule @NotNull [] nativeListInstalledRules() throws IOException;
+
+    public static void addRule(@NotNull final Rule rule) throws IOException {
+        nativeAddRule(rule.ipv6(), rule.fwmark(), rule.priority(), rule.table());
+    }
+
+    public static void deleteRule(@NotNull final Rule rule) throws IOException {
+        nativeDeleteRule(rule.ipv6(), rule.fwmark(), rule.priority(), rule.table());
+    }
+
+    public static @NotNull List<@NotNull Rule> listInstalledRules() throws IOException {
+        return List.of(nativeListInstalledRules());
+    }
+
+    private static native void nativeClearInstalledRoutingPolicy() throws IOException;
+
+    public static void clearInstalledRoutingPolicy() throws IOException {
+        nativeClearInstalledRoutingPolicy();
+    }
+
+    public static final class TunDevice implements AutoCloseable {
+        private final String name;
+        private final List<FileDescriptor> fds;
+        private final Cleaner.Cleanable cleanable;
+
+        private TunDevice(@NotNull final String name, @NotNull final List<@NotNull FileDescriptor> fds) {
+            this.name = name;
+            this.fds = fds;
+            this.cleanable = fdCleaner.register(this, () -> {
+                for (final FileDescriptor fd : fds) {
+                    FileCompat.nativeCloseFileDescriptor(fd);
+                }
+            });
+        }
+
+        public @NotNull String name() {
+            return name;
+        }
+
+        // Descriptors stay open until the device itself is closed or unreachable
+        public @NotNull List<@NotNull FileDescriptor> fds() {
+            return fds;
+        }
+
+        @Override
+        public void close() {
+            cleanable.clean();
+        }
+    }
+
+    public interface Disposable {
+        void dispose();
+    }
+
+    public interface OnProxyChangedListener {
+        void onChanged(@NotNull final SystemProxy proxy);
+    }
+
+    public interface OnNetworkChangedListener {
+        void onChanged(final int changes);
+    }
+
+    @SuppressWarnings("unused")
+    private interface NativeProxyChangedListener {
+        void onChanged(@NotNull final NativeSystemProxy proxy);
+    }
+
+    private static class Holder implements Disposable {
+        private final Cleaner.Cleanable cleanable;
+
+        public Holder(final long ptr) {
+            this.cleanable = holderCleaner.register(this, () -> nativeReleaseHolder(ptr));
+        }
+
+        @Override
+        public void dispose() {
+            cleanable.clean();
+        }
+    }
+
+    public enum OperState {
+        UNKNOWN, NOT_PRESENT, DOWN, LOWER_LAYER_DOWN, TESTING, DORMANT, UP
+    }
+
+    public record InterfaceAddress(@NotNull InetAddress address, int prefixLength) {
+    }
+
+    public record Interface(
+            @NotNull String name,
+            int index,
+            int flags,
+            int type,
+            @Nullable String kind,
+            @NotNull OperState operState,
+            int mtu,
+            byte @Nullable [] hardwareAddress,
+            @NotNull List<@NotNull InterfaceAddress> addresses
+    ) {
+    }
+
+    private record NativeInterface(
+            @NotNull String name,
+            int index,
+            int flags,
+            int type,
+            @Nullable String kind,
+            int operState,
+            int mtu,
+            byte @Nullable [] hardwareAddress,
+            @NotNull String @NotNull [] addresses
+    ) {
+    }
+
+    public record Route(
+            @NotNull InterfaceAddress destination,
+            @Nullable InetAddress gateway,
+            int interfaceIndex,
+            @NotNull String interfaceName,
+            int metric,
+            int table
+    ) {
+    }
+
+    public record RouteConfig(
+            @NotNull InterfaceAddress destination,
+            @Nullable InetAddress gateway,
+            @NotNull String interfaceName,
+            int metric,
+            int table
+    ) {
+    }
+
+    public record Rule(boolean ipv6, int fwmark, int priority, int table) {
+    }
+
+    private record NativeRoute(
+            @NotNull String destination,
+            @Nullable String gateway,
+            int interfaceIndex,
+            @NotNull String interfaceName,
+            int metric,
+            int table
+    ) {
+    }
+
+    public enum Protocol {
+        TCP, UDP
+    }
+
+    // TCP is probed the way Go listens, with SO_REUSEADDR outside windows, reuseAddress only affects UDP
+    public record BindRequest(
+            @NotNull InetSocketAddress address,
+            @NotNull Set<@NotNull Protocol> protocols,
+            boolean reuseAddress
+    ) {
+    }
+
+    public record BindResult(@NotNull BindRequest request, @Nullable String error, int suggestedPort) {
+        public boolean isAvailable() {
+            return error == null;
+        }
+    }
+
+    private record NativeBindResult(@Nullable String error, int suggestedPort) {
+    }
+
+    public record PortOwner(int pid, @Nullable String executable, @NotNull List<@NotNull String> commandLine) {
+    }
+
+    private record NativePortOwner(int pid, @Nullable String executable, @NotNull String @NotNull [] commandLine) {
+    }
+
+    public enum ProxyMode {
+        NONE, MANUAL, AUTO
+    }
+
+    public record ProxyEndpoint(@NotNull String host, int port) {
+    }
+
+    public record SystemProxyConfig(
+            @Nullable ProxyEndpoint http,
+            @Nullable ProxyEndpoint https,
+            @Nullable ProxyEndpoint ftp,
+            @Nullable ProxyEndpoint socks,
+            @Nullable String autoConfigUrl,
+            @NotNull List<@NotNull String> excludes
+    ) {
+        public static @NotNull SystemProxyConfig autoConfig(
+                @NotNull final String url,
+                @NotNull final List<@NotNull String> excludes
+        ) {
+            return new SystemProxyConfig(null, null, null, null, url, excludes);
+        }
+
+        private @NotNull String @NotNull [] hosts() {
+            return Stream.of(http, https, ftp, socks).map(e -> e != null ? e.host() : "").toArray(String[]::new);
+        }
+
+        private int @NotNull [] ports() {
+            return Stream.of(http, https, ftp, socks).mapToInt(e -> e != null ? e.port() : 0).toArray();
+        }
+    }
+
+    public record ExcludeResult(@NotNull String exclude, @Nullable String normalized, @Nullable String error) {
+        public boolean isValid() {
+            return error == null;
+        }
+    }
+
+    private record NativeExcludeResult(@Nullable String normalized, @Nullable String error) {
+    }
+
+    public record SystemProxy(
+            @NotNull ProxyMode mode,
+            @Nullable ProxyEndpoint http,
+            @Nullable ProxyEndpoint https,
+            @Nullable ProxyEndpoint ftp,
+            @Nullable ProxyEndpoint socks,
+            @Nullable String autoConfigUrl,
+            @NotNull List<@NotNull String> excludes
+    ) {
+    }
+
+    private record NativeSystemProxy(
+            int mode,
+            @NotNull String @NotNull [] hosts,
+            int @NotNull [] ports,
+            @Nullable String autoConfigUrl,
+            @NotNull String @NotNull [] excludes
+    ) {
     }
 }
@@ -0,0 +1,115 @@
+package com.github.kr328.clash.compat;
+
+import org.jetbrains.annotations.NotNull;
+import org.jetbrains.annotations.Nullable;
+
+import java.io.IOException;
+import java.lang.ref.Cleaner;
+import java.nio.ByteBuffer;
+import java.nio.charset.CharacterCodingException;
+import java.nio.charset.Charset;
+import java.nio.charset.CodingErrorAction;
+import java.nio.file.InvalidPathException;
+import java.nio.file.Path;
+import java.util.List;
+
+public final class WatcherCompat {
+    public static final int EVENT_CREATE = 1;
+    public static final int EVENT_MODIFY = 1 << 1;
+    public static final int EVENT_DELETE = 1 << 2;
+    public static final int EVENT_MOVE = 1 << 3;
+    public static final int EVENT_ALL = EVENT_CREATE | EVENT_MODIFY | EVENT_DELETE | EVENT_MOVE;
+
+    private static final Cleaner holderCleaner = Cleaner.create();
+    private static final Charset pathCharset = pathCharset();
+
+    static {
+        CompatLibrary.load();
+    }
+
+    private static native boolean nativeIsSupported();
+
+    public static boolean isSupported() {
+        return nativeIsSupported();
+    }
+
+    private static native long nativeAddWatcher(
+            @NotNull final NativeWatch @NotNull [] watches,
+            @NotNull final NativeListener listener
+    ) throws IOException;
+
+    private static native void nativeReleaseWatcher(long ptr);
+
+    @NotNull
+    public static Disposable addWatcher(
+            @NotNull final List<@NotNull Watch> watches,
+            @NotNull final OnChangedListener listener
+    ) throws IOException {
+        final NativeWatch[] nativeWatches = watches.stream()
+                .map(w -> new NativeWatch(w.path.toAbsolutePath().toString(), w.events))
+                .toArray(NativeWatch[]::new);
+
+        return new Holder(nativeAddWatcher(nativeWatches, (path, events) -> {
+            final Path decoded = decodePath(path);
+            if (decoded != null) {
+                listener.onChanged(decoded, events);
+            }
+        }));
+    }
+
+    @NotNull
+    private static Charset pathCharset() {
+        try {
+            return Charset.forName(System.getProperty("sun.jnu.encoding"));
+        } catch (final IllegalArgumentException e) {
+            return Charset.defaultCharset();
+        }
+    }
+
+    // Names are decoded the way java.io maps them, those not representable in that encoding can not be opened
+    // from java anyway and are dropped
+    @Nullable
+    private static Path decodePath(final byte @NotNull [] path) {
+        try {
+            return Path.of(pathCharset.newDecoder()
+                    .onMalformedInput(CodingErrorAction.REPORT)
+                    .onUnmappableCharacter(CodingErrorAction.REPORT)
+                    .decode(ByteBuffer.wrap(path))
+                    .toString());
+        } catch (final CharacterCodingException | InvalidPathException e) {
+            return null;
+        }
+    }
+
+    public interface Disposable {
+        void dispose();
+    }
+
+    public interface OnChangedListener {
+        void onChanged(@NotNull final Path path, final int events);
+    }
+
+    @SuppressWarnings("unused")
+    private interface NativeListener {
+        void onChanged(final byte @NotNull [] path, final int events);
+    }
+
+    public record Watch(@NotNull Path path, int events) {
+    }
+
+    private record NativeWatch(@NotNull String path, int events) {
+    }
+
+    private static class Holder implements Disposable {
+        private final Cleaner.Cleanable cleanable;
+
+        public Holder(final long ptr) {
+            this.cleanable = holderCleaner.register(this, () -> nativeReleaseWatcher(ptr));
+        }
+
+        @Override
+        public void dispose() {
+            cleanable.clean();
+        }
+    }
+}
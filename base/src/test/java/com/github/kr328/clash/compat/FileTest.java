@@ -1,14 +1,32 @@
 package com.github.kr328.clash.compat;
 
 import org.junit.jupiter.api.Assertions;
+import org.junit.jupiter.api.Assumptions;
 import org.junit.jupiter.api.Test;
 
 import java.io.FileDescriptor;
+import java.io.FileInputStream;
+import java.io.FileOutputStream;
 import java.io.IOException;
+import java.io.RandomAccessFile;
 import java.lang.reflect.Field;
+import java.net.InetAddress;
+import java.net.InetSocketAddress;
+import java.net.StandardProtocolFamily;
 import java.nio.ByteBuffer;
+import java.nio.channels.DatagramChannel;
+import java.nio.channels.Pipe.SinkChannel;
+import java.nio.channels.Pipe.SourceChannel;
+import java.nio.channels.ServerSocketChannel;
 import java.nio.channels.SocketChannel;
 import java.nio.charset.StandardCharsets;
+import java.nio.file.Files;
+import java.nio.file.LinkOption;
+import java.nio.file.Path;
+import java.nio.file.attribute.PosixFilePermissions;
+import java.time.Duration;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.TimeUnit;
 
 public class FileTest {
     @Test
@@ -51,6 +69,97 @@ public class FileTest {
         }
     }
 
+    @Test
+    public void newServerSocketChannel() throws IOException, ReflectiveOperationException {
+        final ServerSocketChannel origin = ServerSocketChannel.open();
+        origin.bind(new InetSocketAddress(InetAddress.getLoopbackAddress(), 0));
+
+        final Field fFd = origin.getClass().getDeclaredField("fd");
+        fFd.setAccessible(true);
+
+        try (final ServerSocketChannel channel = FileCompat.newServerSocketChannel((FileDescriptor) fFd.get(origin))) {
+            Assertions.assertEquals(origin.getLocalAddress(), channel.getLocalAddress());
+
+            try (final SocketChannel client = SocketChannel.open(channel.getLocalAddress());
+                 final SocketChannel accepted = channel.accept()) {
+                Assertions.assertEquals(client.getLocalAddress(), accepted.getRemoteAddress());
+            }
+        }
+    }
+
+    @Test
+    public void newDatagramChannel() throws IOException, ReflectiveOperationException {
+        final DatagramChannel origin = DatagramChannel.open();
+        origin.bind(new InetSocketAddress(InetAddress.getLoopbackAddress(), 0));
+
+        final Field fFd = origin.getClass().getDeclaredField("fd");
+        fFd.setAccessible(true);
+
+        try (final DatagramChannel channel = FileCompat.newDatagramChannel((FileDescriptor) fFd.get(origin))) {
+            final ByteBuffer buffer = ByteBuffer.allocate(64);
+
+            channel.send(StandardCharsets.UTF_8.encode("114514"), channel.getLocalAddress());
+            channel.receive(buffer);
+
+            Assertions.assertEquals("114514", StandardCharsets.UTF_8.decode(buffer.flip()).toString());
+        }
+
+        try (final FileCompat.Pipe pipe = FileCompat.createPipe()) {
+            Assertions.assertThrows(IOException.class, () -> FileCompat.newDatagramChannel(pipe.reader().getFD()));
+        }
+
+        // adopted descriptors are taken as inet6 when ipv6 is available, inet ones must be rejected then
+        final DatagramChannel inet = DatagramChannel.open(StandardProtocolFamily.INET);
+        inet.bind(new InetSocketAddress(InetAddress.getByName("127.0.0.1"), 0));
+
+        final FileDescriptor fd = (FileDescriptor) fFd.get(inet);
+        if (isIPv6Available()) {
+            try (inet) {
+                Assertions.assertThrows(IOException.class, () -> FileCompat.newDatagramChannel(fd));
+            }
+        } else {
+            try (final DatagramChannel channel = FileCompat.newDatagramChannel(fd)) {
+                Assertions.assertEquals(inet.getLocalAddress(), channel.getLocalAddress());
+            }
+        }
+    }
+
+    private static boolean isIPv6Available() {
+        try (final DatagramChannel ignored = DatagramChannel.open(StandardProtocolFamily.INET6)) {
+            return true;
+        } catch (final IOException | UnsupportedOperationException e) {
+            return false;
+        }
+    }
+
+    @Test
+    public void newPipeChannels() throws IOException, ReflectiveOperationException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final FileCompat.Pipe pipe = FileCompat.createPipe();
+
+        try (final SourceChannel source = FileCompat.newPipeSourceChannel(pipe.reader().getFD());
+             final SinkChannel sink = FileCompat.newPipeSinkChannel(pipe.writer().getFD())) {
+            final ByteBuffer buffer = ByteBuffer.allocate(64);
+
+            sink.write(StandardCharsets.UTF_8.encode("114514"));
+            source.read(buffer);
+
+            Assertions.assertEquals("114514", StandardCharsets.UTF_8.decode(buffer.flip()).toString());
+        }
+
+        Assertions.assertThrows(IOException.class, () -> FileCompat.newPipeSourceChannel(pipe.writer().getFD()));
+        Assertions.assertThrows(IOException.class, () -> FileCompat.newPipeSinkChannel(pipe.reader().getFD()));
+
+        try (final DatagramChannel channel = DatagramChannel.open()) {
+            final Field fFd = channel.getClass().getDeclaredField("fd");
+            fFd.setAccessible(true);
+
+            final FileDescriptor fd = (FileDescriptor) fFd.get(channel);
+            Assertions.assertThrows(IOException.class, () -> FileCompat.newPipeSourceChannel(fd));
+        }
+    }
+
     @Test
     public void getFileDescriptorFromSocketChannel() throws IOException, ReflectiveOperationException {
         try (final SocketChannel channel = SocketChannel.open()) {
@@ -96,4 +205,130 @@ public class FileTest {
             FileCompat.setFileDescriptorInheritable(fdObj, false);
         }
     }
+
+    @Test
+    public void createSealedFile() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final byte[] content = "mixed-port: 7890".getBytes(StandardCharsets.UTF_8);
+
+        try (final FileCompat.SealedFile file = FileCompat.createSealedFile("config.yaml", content)) {
+            Assertions.assertArrayEquals(content, new FileInputStream(file.fd()).readAllBytes());
+            Assertions.assertThrows(IOException.class, () -> new FileOutputStream(file.fd()).write(content));
+        }
+    }
+
+    @Test
+    public void lockFile() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final Path path = Files.createTempFile("compat-lock-", ".yaml");
+
+        try (
+                final RandomAccessFile first = new RandomAccessFile(path.toFile(), "rw");
+                final RandomAccessFile second = new RandomAccessFile(path.toFile(), "rw")
+        ) {
+            try (final FileCompat.FileLock ignored = FileCompat.lockFile(first.getFD(), true)) {
+                Assertions.assertNull(FileCompat.tryLockFile(second.getFD(), false));
+                Assertions.assertNull(FileCompat.tryLockFile(second.getFD(), true, Duration.ofMillis(100)));
+            }
+
+            try (final FileCompat.FileLock lock = FileCompat.tryLockFile(second.getFD(), false)) {
+                Assertions.assertNotNull(lock);
+
+                try (final FileCompat.FileLock shared = FileCompat.tryLockFile(first.getFD(), false)) {
+                    Assertions.assertNotNull(shared);
+                }
+
+                Assertions.assertNull(FileCompat.tryLockFile(first.getFD(), true));
+            }
+        } finally {
+            Files.delete(path);
+        }
+    }
+
+    @Test
+    public void writeFileAtomically() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final Path directory = Files.createTempDirectory("compat-atomic-");
+        final Path path = directory.resolve("config.yaml");
+        final Path backup = directory.resolve("config.yaml.bak");
+
+        try {
+            FileCompat.writeFileAtomically(path, "mixed-port: 7890".getBytes(StandardCharsets.UTF_8), null);
+            Assertions.assertEquals("mixed-port: 7890", Files.readString(path));
+
+            FileCompat.writeFileAtomically(path, "mixed-port: 7891".getBytes(StandardCharsets.UTF_8), backup);
+            Assertions.assertEquals("mixed-port: 7891", Files.readString(path));
+            Assertions.assertEquals("mixed-port: 7890", Files.readString(backup));
+        } finally {
+            Files.deleteIfExists(path);
+            Files.deleteIfExists(backup);
+            Files.delete(directory);
+        }
+    }
+
+    @Test
+    public void startForward() throws Exception {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        try (
+                final FileCompat.Pipe source = FileCompat.createPipe();
+                final FileCompat.Pipe target = FileCompat.createPipe()
+        ) {
+            final CompletableFuture<Long> closed = new CompletableFuture<>();
+
+            try (final FileCompat.Forwarder forwarder = FileCompat.startForward(
+                    source.reader().getFD(),
+                    target.writer().getFD(),
+                    1024 * 1024,
+                    (transferred, error) -> {
+                        if (error != null) {
+                            closed.completeExceptionally(new IOException(error));
+                        } else {
+                            closed.complete(transferred);
+                        }
+                    }
+            )) {
+                final byte[] testData = "114514".getBytes(StandardCharsets.UTF_8);
+
+                final byte[] buffer = new byte[64];
+
+                source.writer().write(testData);
+                final int length = target.reader().read(buffer);
+
+                Assertions.assertEquals(new String(testData, StandardCharsets.UTF_8), new String(buffer, 0, length, StandardCharsets.UTF_8));
+
+                source.writer().close();
+
+                Assertions.assertEquals(testData.length, closed.get(5, TimeUnit.SECONDS));
+                Assertions.assertEquals(testData.length, forwarder.getTransferred());
+
+                forwarder.close();
+
+                Assertions.assertEquals(testData.length, forwarder.getTransferred());
+            }
+        }
+    }
+
+    @Test
+    public void createPrivateDirectory() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final Path path;
+        try (final FileCompat.PrivateDirectory directory = FileCompat.createPrivateDirectory("compat-test")) {
+            path = directory.getPath();
+
+            Assertions.assertEquals(
+                    PosixFilePermissions.fromString("rwx------"),
+                    Files.getPosixFilePermissions(path, LinkOption.NOFOLLOW_LINKS)
+            );
+
+            Files.createDirectories(path.resolve("cache"));
+            Files.writeString(path.resolve("cache").resolve("data"), "114514");
+        }
+
+        Assertions.assertFalse(Files.exists(path, LinkOption.NOFOLLOW_LINKS));
+    }
 }
@@ -5,7 +5,16 @@ import org.junit.jupiter.api.Assumptions;
 import org.junit.jupiter.api.Test;
 
 import java.io.IOException;
+import java.net.InetAddress;
+import java.net.InetSocketAddress;
+import java.net.NetworkInterface;
+import java.net.ServerSocket;
+import java.nio.file.Files;
+import java.nio.file.Path;
 import java.util.List;
+import java.util.Set;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.TimeUnit;
 
 public class NetworkTest {
     @Test
@@ -20,4 +29,274 @@ public class NetworkTest {
 
         Assertions.assertTrue(Window.showIsSuccessWindow("Remove System Proxy"));
     }
+
+    @Test
+    public void getSystemProxy() throws IOException {
+        Assumptions.assumeTrue(NetworkCompat.isSystemProxySupported());
+
+        NetworkCompat.setSystemProxy(true, "127.0.0.1:8080", List.of("127.0.0.1", "localhost"));
+
+        final NetworkCompat.SystemProxy proxy = NetworkCompat.getSystemProxy();
+        Assertions.assertEquals(NetworkCompat.ProxyMode.MANUAL, proxy.mode());
+        Assertions.assertEquals(new NetworkCompat.ProxyEndpoint("127.0.0.1", 8080), proxy.http());
+        Assertions.assertEquals(new NetworkCompat.ProxyEndpoint("127.0.0.1", 8080), proxy.https());
+        Assertions.assertEquals(List.of("127.0.0.1", "localhost"), proxy.excludes());
+
+        NetworkCompat.setSystemProxy(false, "", List.of());
+
+        Assertions.assertEquals(NetworkCompat.ProxyMode.NONE, NetworkCompat.getSystemProxy().mode());
+    }
+
+    @Test
+    public void setPerProtocolSystemProxy() throws IOException {
+        Assumptions.assumeTrue(NetworkCompat.isSystemProxySupported());
+
+        final NetworkCompat.ProxyEndpoint http = new NetworkCompat.ProxyEndpoint("proxy.lan", 7890);
+        final NetworkCompat.ProxyEndpoint socks = new NetworkCompat.ProxyEndpoint("::1", 7891);
+
+        NetworkCompat.setSystemProxy(true, new NetworkCompat.SystemProxyConfig(http, http, null, socks, null, List.of("localhost")));
+
+        final NetworkCompat.SystemProxy proxy = NetworkCompat.getSystemProxy();
+        Assertions.assertEquals(NetworkCompat.ProxyMode.MANUAL, proxy.mode());
+        Assertions.assertEquals(http, proxy.http());
+        Assertions.assertEquals(http, proxy.https());
+        Assertions.assertNull(proxy.ftp());
+        Assertions.assertEquals(socks, proxy.socks());
+
+        NetworkCompat.setSystemProxy(false, "", List.of());
+    }
+
+    @Test
+    public void setAutoConfigSystemProxy() throws IOException {
+        Assumptions.assumeTrue(NetworkCompat.isSystemProxySupported());
+
+        final String url = "http://127.0.0.1:7890/proxy.pac";
+
+        NetworkCompat.setSystemProxy(true, NetworkCompat.SystemProxyConfig.autoConfig(url, List.of()));
+
+        final NetworkCompat.SystemProxy proxy = NetworkCompat.getSystemProxy();
+        Assertions.assertEquals(NetworkCompat.ProxyMode.AUTO, proxy.mode());
+        Assertions.assertEquals(url, proxy.autoConfigUrl());
+
+        NetworkCompat.setSystemProxy(false, "", List.of());
+    }
+
+    @Test
+    public void proxyChangedListener() throws Exception {
+        Assumptions.assumeTrue(NetworkCompat.isSystemProxySupported());
+
+        final NetworkCompat.ProxyEndpoint endpoint = new NetworkCompat.ProxyEndpoint("127.0.0.1", 7890);
+        final NetworkCompat.SystemProxyConfig config = new NetworkCompat.SystemProxyConfig(
+                endpoint, endpoint, null, null, null, List.of("localhost")
+        );
+        final CompletableFuture<NetworkCompat.SystemProxy> changed = new CompletableFuture<>();
+
+        NetworkCompat.setSystemProxy(true, config);
+
+        final NetworkCompat.Disposable disposable = NetworkCompat.addProxyChangedListener(config, changed::complete);
+        try {
+            NetworkCompat.setSystemProxy(true, NetworkCompat.SystemProxyConfig.autoConfig("http://127.0.0.1/proxy.pac", List.of()));
+
+            Assertions.assertNotNull(changed.get(10, TimeUnit.SECONDS));
+
+            Thread.sleep(1000);
+
+            Assertions.assertEquals(endpoint, NetworkCompat.getSystemProxy().http());
+        } finally {
+            disposable.dispose();
+
+            NetworkCompat.setSystemProxy(false, "", List.of());
+        }
+    }
+
+    @Test
+    public void listInterfaces() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final List<NetworkCompat.Interface> interfaces = NetworkCompat.listInterfaces();
+
+        for (final NetworkInterface expected : NetworkInterface.networkInterfaces().toList()) {
+            final NetworkCompat.Interface actual = interfaces.stream()
+                    .filter(i -> i.name().equals(expected.getName()))
+                    .findFirst()
+                    .orElseThrow();
+
+            Assertions.assertEquals(expected.getIndex(), actual.index());
+            Assertions.assertEquals(expected.getMTU(), actual.mtu());
+        }
+    }
+
+    @Test
+    public void getDefaultRoutes() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        final List<NetworkCompat.Interface> interfaces = NetworkCompat.listInterfaces();
+
+        for (final NetworkCompat.Route route : NetworkCompat.getDefaultRoutes()) {
+            Assertions.assertEquals(0, route.destination().prefixLength());
+            Assertions.assertTrue(interfaces.stream().anyMatch(i -> i.index() == route.interfaceIndex()));
+        }
+    }
+
+    @Test
+    public void routingPolicy() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+        Assumptions.assumeTrue(SecurityCompat.getUnixUid() == 0);
+
+        final NetworkCompat.RouteConfig route = new NetworkCompat.RouteConfig(
+                new NetworkCompat.InterfaceAddress(InetAddress.getByName("198.18.0.0"), 15),
+                null,
+                "lo",
+                0,
+                31337
+        );
+        final NetworkCompat.Rule rule = new NetworkCompat.Rule(false, 0x7c7c, 31337, 31337);
+
+        try {
+            NetworkCompat.addRoute(route);
+            NetworkCompat.addRule(rule);
+
+            Assertions.assertTrue(NetworkCompat.listInstalledRoutes().stream().anyMatch(r -> r.table() == route.table()));
+            Assertions.assertTrue(NetworkCompat.listInstalledRules().contains(rule));
+
+            NetworkCompat.deleteRule(rule);
+
+            Assertions.assertFalse(NetworkCompat.listInstalledRules().contains(rule));
+        } finally {
+            NetworkCompat.clearInstalledRoutingPolicy();
+        }
+
+        Assertions.assertTrue(NetworkCompat.listInstalledRoutes().isEmpty());
+    }
+
+    @Test
+    public void networkChangedListener() throws Exception {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+        Assumptions.assumeTrue(SecurityCompat.getUnixUid() == 0);
+
+        final CompletableFuture<Integer> changed = new CompletableFuture<>();
+        final NetworkCompat.Disposable disposable = NetworkCompat.addNetworkChangedListener(changed::complete);
+        try {
+            final NetworkCompat.RouteConfig route = new NetworkCompat.RouteConfig(
+                    new NetworkCompat.InterfaceAddress(InetAddress.getByName("198.19.0.0"), 16),
+                    null,
+                    "lo",
+                    0,
+                    31338
+            );
+
+            NetworkCompat.addRoute(route);
+            NetworkCompat.deleteRoute(route);
+
+            Assertions.assertNotEquals(0, changed.get(10, TimeUnit.SECONDS) & NetworkCompat.CHANGE_ROUTE);
+        } finally {
+            disposable.dispose();
+        }
+    }
+
+    @Test
+    public void setLinkDns() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+        Assumptions.assumeTrue(SecurityCompat.getUnixUid() == 0);
+        Assumptions.assumeTrue(Files.isDirectory(Path.of("/run/systemd/resolve")));
+
+        try (final NetworkCompat.TunDevice device = NetworkCompat.createTun("compat1", 1500, List.of("198.18.0.1/30"), 1)) {
+            final int index = NetworkInterface.getByName(device.name()).getIndex();
+
+            NetworkCompat.setLinkDns(true, index, List.of(InetAddress.getByName("198.18.0.2")));
+            NetworkCompat.setLinkDns(false, index, List.of());
+        }
+    }
+
+    @Test
+    public void findPortOwners() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+
+        try (final ServerSocket socket = new ServerSocket(0, 1, InetAddress.getLoopbackAddress())) {
+            final List<NetworkCompat.PortOwner> owners = NetworkCompat.findPortOwners(
+                    NetworkCompat.Protocol.TCP,
+                    socket.getLocalPort()
+            );
+
+            Assertions.assertEquals(1, owners.size());
+            Assertions.assertEquals(ProcessHandle.current().pid(), owners.get(0).pid());
+        }
+    }
+
+    @Test
+    public void validateExcludes() {
+        final List<NetworkCompat.ExcludeResult> results = NetworkCompat.validateExcludes(
+                List.of("<local>", ".Example.com", "10.*", "a'b")
+        );
+
+        Assertions.assertEquals("<local>", results.get(0).normalized());
+        Assertions.assertEquals("*.example.com", results.get(1).normalized());
+        Assertions.assertEquals("10.0.0.0/8", results.get(2).normalized());
+        Assertions.assertFalse(results.get(3).isValid());
+    }
+
+    @Test
+    public void probeBind() throws IOException {
+        try (final ServerSocket socket = new ServerSocket(0, 1, InetAddress.getLoopbackAddress())) {
+            final NetworkCompat.BindRequest request = new NetworkCompat.BindRequest(
+                    new InetSocketAddress(InetAddress.getLoopbackAddress(), socket.getLocalPort()),
+                    Set.of(NetworkCompat.Protocol.TCP),
+                    false
+            );
+
+            final List<NetworkCompat.BindResult> results = NetworkCompat.probeBind(List.of(request), 20000, 30000);
+
+            Assertions.assertEquals(1, results.size());
+            Assertions.assertFalse(results.get(0).isAvailable());
+            Assertions.assertTrue(results.get(0).suggestedPort() >= 20000);
+            Assertions.assertTrue(results.get(0).suggestedPort() <= 30000);
+        }
+    }
+
+    @Test
+    public void setProxyEnvironment() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+        Assumptions.assumeTrue(System.getenv("DBUS_SESSION_BUS_ADDRESS") != null);
+
+        final Path path = Path.of(System.getProperty("user.home"), ".config", "environment.d", "compat-test.conf");
+        final NetworkCompat.ProxyEndpoint http = new NetworkCompat.ProxyEndpoint("127.0.0.1", 7890);
+
+        NetworkCompat.setProxyEnvironment(
+                true,
+                "compat-test",
+                new NetworkCompat.SystemProxyConfig(http, http, null, null, null, List.of("localhost"))
+        );
+
+        Assertions.assertTrue(Files.readString(path).contains("http_proxy=http://127.0.0.1:7890"));
+
+        NetworkCompat.setProxyEnvironment(false, "compat-test", new NetworkCompat.SystemProxyConfig(null, null, null, null, null, List.of()));
+
+        Assertions.assertFalse(Files.exists(path));
+    }
+
+    @Test
+    public void createTun() throws IOException {
+        Assumptions.assumeTrue(System.getProperty("os.name").toLowerCase().contains("linux"));
+        Assumptions.assumeTrue(SecurityCompat.getUnixUid() == 0);
+
+        try (final NetworkCompat.TunDevice device = NetworkCompat.createTun(
+                "compat0",
+                9000,
+                List.of("198.18.0.1/16", "fdfe:dcba:9876::1/126"),
+                2
+        )) {
+            Assertions.assertEquals("compat0", device.name());
+            Assertions.assertEquals(2, device.fds().size());
+
+            final NetworkInterface networkInterface = NetworkInterface.getByName(device.name());
+            Assertions.assertNotNull(networkInterface);
+            Assertions.assertEquals(9000, networkInterface.getMTU());
+            Assertions.assertTrue(networkInterface.isUp());
+        }
+
+        Assertions.assertThrows(
+                IOException.class,
+                () -> NetworkCompat.createTun("compat0", -1, List.of("198.18.0.1/16"), 1)
+        );
+    }
 }
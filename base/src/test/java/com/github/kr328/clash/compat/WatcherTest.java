@@ -0,0 +1,45 @@
+package com.github.kr328.clash.compat;
+
+import org.junit.jupiter.api.Assertions;
+import org.junit.jupiter.api.Assumptions;
+import org.junit.jupiter.api.Test;
+
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.util.List;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.TimeUnit;
+
+public class WatcherTest {
+    @Test
+    void addWatcher() throws Exception {
+        Assumptions.assumeTrue(WatcherCompat.isSupported());
+
+        final Path directory = Files.createTempDirectory("compat-watcher-");
+        final Path config = directory.resolve("config.yaml");
+
+        final CompletableFuture<Integer> events = new CompletableFuture<>();
+        final WatcherCompat.Disposable disposable = WatcherCompat.addWatcher(
+                List.of(new WatcherCompat.Watch(directory, WatcherCompat.EVENT_ALL)),
+                (path, e) -> {
+                    if (path.equals(config)) {
+                        events.complete(e);
+                    }
+                }
+        );
+
+        try {
+            Files.writeString(config, "mixed-port: 7890");
+
+            Assertions.assertEquals(
+                    WatcherCompat.EVENT_CREATE | WatcherCompat.EVENT_MODIFY,
+                    events.get(5, TimeUnit.SECONDS)
+            );
+        } finally {
+            disposable.dispose();
+
+            Files.deleteIfExists(config);
+            Files.delete(directory);
+        }
+    }
+}
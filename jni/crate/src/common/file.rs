@@ -1 +1,35 @@
 pub type FileDescriptor = isize;
+
+pub trait ForwardListener {
+    fn on_closed(&self, transferred: u64, error: Option<&str>);
+}
+
+pub trait Forwarder {
+    fn transferred(&self) -> u64;
+
+    fn stop(&mut self);
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SocketFamily {
+    Inet,
+    Inet6,
+    Unix,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SocketInfo {
+    pub family: SocketFamily,
+    pub socket_type: SocketType,
+    pub listening: bool,
+}
+
+pub trait PrivateDirectory {
+    fn path(&self) -> &str;
+}
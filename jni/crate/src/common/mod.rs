@@ -3,4 +3,5 @@ pub mod network;
 pub mod notifier;
 pub mod shell;
 pub mod theme;
+pub mod watcher;
 pub mod window;
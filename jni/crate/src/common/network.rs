@@ -1,4 +1,334 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
 pub struct SystemProxyConfig {
-    pub address: String,
+    pub http: Option<ProxyEndpoint>,
+    pub https: Option<ProxyEndpoint>,
+    pub ftp: Option<ProxyEndpoint>,
+    pub socks: Option<ProxyEndpoint>,
+    pub auto_config_url: Option<String>,
+    pub excludes: Vec<ProxyExclude>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ProxyExclude {
+    /// Host names without dots and loopback addresses
+    Local,
+    Host(String),
+    /// Subdomains of the domain, written as `*.domain` or `.domain`
+    Wildcard(String),
+    Network(InterfaceAddress),
+}
+
+fn normalize_host(host: &str) -> Result<String, Box<dyn Error>> {
+    let host = host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase();
+
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
+    };
+
+    // Numeric top level labels only show up in malformed addresses like `300.1.1.1`
+    let numeric = host
+        .rsplit('.')
+        .next()
+        .is_some_and(|label| label.bytes().all(|c| c.is_ascii_digit()));
+
+    if host.len() > 253 || numeric || !host.split('.').all(valid_label) {
+        return Err(format!("invalid host name: {host}").into());
+    }
+
+    Ok(host)
+}
+
+fn mask_address(address: IpAddr, prefix_len: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+// Windows style `192.168.*` patterns are accepted as networks on whole octets
+fn parse_octet_wildcard(s: &str) -> Option<InterfaceAddress> {
+    let parts = s.split('.').collect::<Vec<_>>();
+    let octets = parts.iter().take_while(|part| **part != "*").collect::<Vec<_>>();
+
+    if parts.len() > 4 || octets.is_empty() || octets.len() == parts.len() || parts[octets.len()..].iter().any(|p| *p != "*") {
+        return None;
+    }
+
+    let mut address = [0u8; 4];
+    for (index, octet) in octets.iter().enumerate() {
+        address[index] = octet.parse().ok()?;
+    }
+
+    Some(InterfaceAddress {
+        address: IpAddr::V4(Ipv4Addr::from(address)),
+        prefix_len: octets.len() as u8 * 8,
+    })
+}
+
+impl Display for ProxyExclude {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyExclude::Local => write!(f, "<local>"),
+            ProxyExclude::Host(host) => write!(f, "{host}"),
+            ProxyExclude::Wildcard(domain) => write!(f, "*.{domain}"),
+            ProxyExclude::Network(network) => {
+                if network.prefix_len == if network.address.is_ipv4() { 32 } else { 128 } {
+                    write!(f, "{}", network.address)
+                } else {
+                    write!(f, "{network}")
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ProxyExclude {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.eq_ignore_ascii_case("<local>") {
+            return Ok(ProxyExclude::Local);
+        }
+
+        if let Some(network) = parse_octet_wildcard(s) {
+            return Ok(ProxyExclude::Network(network));
+        }
+
+        if s.contains(['/', ':']) {
+            let network = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(s);
+            let network = network
+                .parse::<InterfaceAddress>()
+                .map_err(|_| format!("invalid address: {s}"))?;
+
+            return Ok(ProxyExclude::Network(InterfaceAddress {
+                address: mask_address(network.address, network.prefix_len),
+                prefix_len: network.prefix_len,
+            }));
+        }
+
+        if let Ok(address) = s.parse::<IpAddr>() {
+            return Ok(ProxyExclude::Network(InterfaceAddress {
+                address,
+                prefix_len: if address.is_ipv4() { 32 } else { 128 },
+            }));
+        }
+
+        match s.strip_prefix("*.").or_else(|| s.strip_prefix('.')) {
+            Some(domain) => Ok(ProxyExclude::Wildcard(normalize_host(domain)?)),
+            None => Ok(ProxyExclude::Host(normalize_host(s)?)),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ProxyMode {
+    #[default]
+    None,
+    Manual,
+    Auto,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProxyEndpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Display for ProxyEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SystemProxySettings {
+    pub mode: ProxyMode,
+    pub http: Option<ProxyEndpoint>,
+    pub https: Option<ProxyEndpoint>,
+    pub ftp: Option<ProxyEndpoint>,
+    pub socks: Option<ProxyEndpoint>,
+    pub auto_config_url: Option<String>,
     pub excludes: Vec<String>,
 }
+
+pub struct TunConfig {
+    pub name: String,
+    pub mtu: u32,
+    pub addresses: Vec<String>,
+    pub queues: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum OperState {
+    #[default]
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl Display for InterfaceAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+impl FromStr for InterfaceAddress {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address.parse::<IpAddr>()?, Some(prefix_len.parse::<u8>()?)),
+            None => (s.parse::<IpAddr>()?, None),
+        };
+
+        let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+        match prefix_len {
+            Some(prefix_len) if prefix_len > max_prefix_len => Err(format!("invalid prefix length: {s}").into()),
+            prefix_len => Ok(Self {
+                address,
+                prefix_len: prefix_len.unwrap_or(max_prefix_len),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub index: u32,
+    pub flags: u32,
+    pub link_type: u16,
+    pub kind: Option<String>,
+    pub oper_state: OperState,
+    pub mtu: u32,
+    pub hardware_address: Option<Vec<u8>>,
+    pub addresses: Vec<InterfaceAddress>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Route {
+    pub destination: InterfaceAddress,
+    pub gateway: Option<IpAddr>,
+    pub interface_index: u32,
+    pub interface_name: String,
+    pub metric: u32,
+    pub table: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Rule {
+    pub ipv6: bool,
+    pub fwmark: u32,
+    pub priority: u32,
+    pub table: u32,
+}
+
+pub trait Listener {
+    fn on_changed(&self, settings: &SystemProxySettings);
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub executable: Option<String>,
+    pub command_line: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BindRequest {
+    pub address: SocketAddr,
+    pub tcp: bool,
+    pub udp: bool,
+    pub reuse_address: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BindResult {
+    pub error: Option<String>,
+    pub suggestion: Option<u16>,
+}
+
+pub const CHANGE_LINK: u32 = 1 << 0;
+pub const CHANGE_ADDRESS: u32 = 1 << 1;
+pub const CHANGE_ROUTE: u32 = 1 << 2;
+
+pub trait ChangeListener {
+    fn on_network_changed(&self, changes: u32);
+}
+
+pub trait Holder {}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::network::ProxyExclude;
+
+    #[test]
+    pub fn test_parse_proxy_exclude() {
+        let normalize = |s: &str| s.parse::<ProxyExclude>().map(|e| e.to_string()).ok();
+
+        assert_eq!(normalize("<LOCAL>"), Some("<local>".to_owned()));
+        assert_eq!(normalize(" Example.COM. "), Some("example.com".to_owned()));
+        assert_eq!(normalize(".example.com"), Some("*.example.com".to_owned()));
+        assert_eq!(normalize("*.example.com"), Some("*.example.com".to_owned()));
+        assert_eq!(normalize("192.168.1.5/16"), Some("192.168.0.0/16".to_owned()));
+        assert_eq!(normalize("10.*"), Some("10.0.0.0/8".to_owned()));
+        assert_eq!(normalize("10.1.*.*"), Some("10.1.0.0/16".to_owned()));
+        assert_eq!(normalize("127.0.0.1"), Some("127.0.0.1".to_owned()));
+        assert_eq!(normalize("[::1]"), Some("::1".to_owned()));
+        assert_eq!(normalize("fd00::1/8"), Some("fd00::/8".to_owned()));
+
+        for invalid in [
+            "",
+            "*",
+            "$HOME",
+            "a'b",
+            "\"quoted\"",
+            "-bad.com",
+            "300.1.1.1",
+            "10.0.0.0/33",
+            "10.*.1",
+            "::g",
+        ] {
+            assert!(invalid.parse::<ProxyExclude>().is_err(), "{invalid}");
+        }
+    }
+}
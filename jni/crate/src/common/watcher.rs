@@ -0,0 +1,16 @@
+pub const EVENT_CREATE: u32 = 1 << 0;
+pub const EVENT_MODIFY: u32 = 1 << 1;
+pub const EVENT_DELETE: u32 = 1 << 2;
+pub const EVENT_MOVE: u32 = 1 << 3;
+
+pub struct Watch {
+    pub path: String,
+    pub events: u32,
+}
+
+pub trait Listener {
+    // Path is passed as raw bytes, file names are not required to be valid in any encoding
+    fn on_changed(&self, path: &[u8], events: u32);
+}
+
+pub trait Holder {}
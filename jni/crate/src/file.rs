@@ -1,7 +1,9 @@
-use jni_sys::{jboolean, jclass, jfieldID, jint, jlong, jmethodID, jobject, JNIEnv, JNI_FALSE};
+use std::{error::Error, ptr::null_mut};
+
+use jni_sys::{jboolean, jbyteArray, jclass, jfieldID, jint, jlong, jmethodID, jobject, jstring, JNIEnv, JNI_FALSE, JNI_TRUE};
 
 use crate::{
-    common::file::FileDescriptor,
+    common::file::{FileDescriptor, Forwarder, PrivateDirectory, SocketFamily, SocketInfo, SocketType},
     helper::{
         call::jcall,
         lazy::{JRef, LazyJRef},
@@ -33,7 +35,7 @@ pub fn get_file_descriptor(env: *mut JNIEnv, fd: jobject) -> FileDescriptor {
     }
 }
 
-fn set_file_descriptor(env: *mut JNIEnv, fd: jobject, value: FileDescriptor, is_socket: bool) {
+pub fn set_file_descriptor(env: *mut JNIEnv, fd: jobject, value: FileDescriptor, is_socket: bool) {
     #[cfg(windows)]
     if is_socket {
         jcall!(env, SetIntField, fd, *F_FILE_DESCRIPTOR_FD.get(), value as jint);
@@ -131,6 +133,252 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeCreateUnix
     });
 }
 
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeCreateSealedFile(
+    env: *mut JNIEnv,
+    _: jclass,
+    name: jstring,
+    content: jbyteArray,
+    fd: jobject,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (name, content, fd);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let name = crate::helper::strings::java_string_to_string(env, name);
+        let content = crate::helper::array::collect_java_bytes(env, content);
+
+        let sealed = crate::linux::file::create_sealed_file(&name, &content)?;
+
+        set_file_descriptor(env, fd, sealed, false);
+
+        Ok(())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeLockFile(
+    env: *mut JNIEnv,
+    _: jclass,
+    fd: jobject,
+    exclusive: jboolean,
+    timeout_millis: jlong,
+) -> jboolean {
+    #[cfg(windows)]
+    {
+        let _ = (fd, exclusive, timeout_millis);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        JNI_FALSE
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let timeout = if timeout_millis >= 0 {
+            Some(std::time::Duration::from_millis(timeout_millis as u64))
+        } else {
+            None
+        };
+
+        if crate::linux::file::lock_file(get_file_descriptor(env, fd), exclusive != JNI_FALSE, timeout)? {
+            Ok(JNI_TRUE)
+        } else {
+            Ok(JNI_FALSE)
+        }
+    })
+    .unwrap_or(JNI_FALSE)
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeUnlockFile(env: *mut JNIEnv, _: jclass, fd: jobject) {
+    #[cfg(windows)]
+    {
+        let _ = fd;
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || crate::linux::file::unlock_file(get_file_descriptor(env, fd)));
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeWriteFileAtomically(
+    env: *mut JNIEnv,
+    _: jclass,
+    path: jstring,
+    content: jbyteArray,
+    backup: jstring,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (path, content, backup);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let path = crate::helper::strings::java_string_to_string(env, path);
+        let content = crate::helper::array::collect_java_bytes(env, content);
+        let backup = if !backup.is_null() {
+            Some(crate::helper::strings::java_string_to_string(env, backup))
+        } else {
+            None
+        };
+
+        crate::linux::file::write_file_atomically(
+            std::path::Path::new(&path),
+            &content,
+            backup.as_ref().map(std::path::Path::new),
+        )
+    });
+}
+
+#[cfg(target_os = "linux")]
+static M_FORWARD_LISTENER_ON_CLOSED: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        "com/github/kr328/clash/compat/FileCompat$ForwardListener",
+        "onClosed",
+        "(JLjava/lang/String;)V",
+    ))
+});
+
+#[cfg(target_os = "linux")]
+struct ForwardListenerImpl {
+    listener_ref: crate::helper::refs::GlobalRef,
+}
+
+#[cfg(target_os = "linux")]
+impl crate::common::file::ForwardListener for ForwardListenerImpl {
+    fn on_closed(&self, transferred: u64, error: Option<&str>) {
+        let env = crate::helper::vm::attach_current_thread();
+        let error = match error {
+            Some(error) => crate::helper::strings::string_to_java_string(*env, error),
+            None => null_mut(),
+        };
+
+        jcall!(
+            *env,
+            CallVoidMethod,
+            *self.listener_ref,
+            *M_FORWARD_LISTENER_ON_CLOSED.get(),
+            transferred as jlong,
+            error
+        );
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeStartForward(
+    env: *mut JNIEnv,
+    _: jclass,
+    source: jobject,
+    target: jobject,
+    pipe_capacity: jint,
+    listener: jobject,
+) -> jlong {
+    #[cfg(windows)]
+    {
+        let _ = (source, target, pipe_capacity, listener);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        -1
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let listener = ForwardListenerImpl {
+            listener_ref: crate::helper::refs::GlobalRef::new(listener),
+        };
+
+        let forwarder = crate::linux::file::start_forward(
+            get_file_descriptor(env, source),
+            get_file_descriptor(env, target),
+            pipe_capacity.max(0) as usize,
+            listener,
+        )?;
+
+        Ok(Box::into_raw(Box::new(forwarder)) as jlong)
+    })
+    .unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeGetForwardTransferred(
+    _: *mut JNIEnv,
+    _: jclass,
+    ptr: jlong,
+) -> jlong {
+    unsafe { (*(ptr as *mut Box<dyn Forwarder>)).transferred() as jlong }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeStopForward(
+    _: *mut JNIEnv,
+    _: jclass,
+    ptr: jlong,
+) -> jlong {
+    let mut forwarder = unsafe { Box::from_raw(ptr as *mut Box<dyn Forwarder>) };
+
+    forwarder.stop();
+
+    forwarder.transferred() as jlong
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeCreatePrivateDirectory(
+    env: *mut JNIEnv,
+    _: jclass,
+    prefix: jstring,
+) -> jlong {
+    #[cfg(windows)]
+    {
+        let _ = prefix;
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        -1
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let prefix = crate::helper::strings::java_string_to_string(env, prefix);
+
+        let directory = crate::linux::file::create_private_directory(&prefix)?;
+
+        Ok(Box::into_raw(Box::new(directory)) as jlong)
+    })
+    .unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeGetPrivateDirectoryPath(
+    env: *mut JNIEnv,
+    _: jclass,
+    ptr: jlong,
+) -> jstring {
+    let directory = unsafe { &*(ptr as *mut Box<dyn PrivateDirectory>) };
+
+    crate::helper::strings::string_to_java_string(env, directory.path())
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeReleasePrivateDirectory(
+    _: *mut JNIEnv,
+    _: jclass,
+    ptr: jlong,
+) {
+    unsafe { drop(Box::from_raw(ptr as *mut Box<dyn PrivateDirectory>)) }
+}
+
 static C_SOCKET_CHANNEL_IMPL: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/SocketChannelImpl"));
 static M_NEW_SOCKET_CHANNEL_IMPL: LazyJRef<jmethodID> = LazyJRef::new(|| {
     JRef::from((
@@ -160,3 +408,196 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeNewSocketC
         address
     )
 }
+
+static C_STANDARD_PROTOCOL_FAMILY: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("java/net/StandardProtocolFamily"));
+static O_PROTOCOL_FAMILY_INET: LazyJRef<jobject> = LazyJRef::new(|| protocol_family("INET"));
+static O_PROTOCOL_FAMILY_INET6: LazyJRef<jobject> = LazyJRef::new(|| protocol_family("INET6"));
+static O_PROTOCOL_FAMILY_UNIX: LazyJRef<jobject> = LazyJRef::new(|| protocol_family("UNIX"));
+
+fn protocol_family(name: &str) -> JRef<jobject> {
+    let field: JRef<jfieldID> = (&C_STANDARD_PROTOCOL_FAMILY, name, "Ljava/net/StandardProtocolFamily;", ()).into();
+
+    JRef::from((&C_STANDARD_PROTOCOL_FAMILY, field, ()))
+}
+
+fn get_socket_info(env: *mut JNIEnv, fd: jobject) -> Result<SocketInfo, Box<dyn Error>> {
+    let fd = get_file_descriptor(env, fd);
+
+    #[cfg(windows)]
+    return crate::win32::file::get_socket_info(fd);
+
+    #[cfg(target_os = "linux")]
+    return crate::linux::file::get_socket_info(fd);
+}
+
+static C_SERVER_SOCKET_CHANNEL_IMPL: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/ServerSocketChannelImpl"));
+static M_NEW_SERVER_SOCKET_CHANNEL_IMPL: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_SERVER_SOCKET_CHANNEL_IMPL,
+        "<init>",
+        "(Ljava/nio/channels/spi/SelectorProvider;Ljava/net/ProtocolFamily;Ljava/io/FileDescriptor;Z)V",
+    ))
+});
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeNewServerSocketChannel(
+    env: *mut JNIEnv,
+    _: jclass,
+    sp: jobject,
+    fd: jobject,
+) -> jobject {
+    rethrow_java_io_exception(env, || {
+        let info = get_socket_info(env, fd)?;
+        if info.socket_type != SocketType::Stream || !info.listening {
+            return Err("not a listening stream socket".into());
+        }
+
+        let family = match info.family {
+            SocketFamily::Inet => O_PROTOCOL_FAMILY_INET.get(),
+            SocketFamily::Inet6 => O_PROTOCOL_FAMILY_INET6.get(),
+            SocketFamily::Unix => O_PROTOCOL_FAMILY_UNIX.get(),
+        };
+
+        Ok(jcall!(
+            env,
+            NewObject,
+            *C_SERVER_SOCKET_CHANNEL_IMPL.get(),
+            *M_NEW_SERVER_SOCKET_CHANNEL_IMPL.get(),
+            sp,
+            *family,
+            fd,
+            JNI_TRUE as jint
+        ))
+    })
+    .unwrap_or(null_mut())
+}
+
+static C_DATAGRAM_CHANNEL_IMPL: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/DatagramChannelImpl"));
+static M_NEW_DATAGRAM_CHANNEL_IMPL: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_DATAGRAM_CHANNEL_IMPL,
+        "<init>",
+        "(Ljava/nio/channels/spi/SelectorProvider;Ljava/io/FileDescriptor;)V",
+    ))
+});
+
+static C_NET: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/Net"));
+static M_NET_IS_IPV6_AVAILABLE: LazyJRef<jmethodID> = LazyJRef::new(|| JRef::from((&C_NET, "isIPv6Available", "()Z", ())));
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeNewDatagramChannel(
+    env: *mut JNIEnv,
+    _: jclass,
+    sp: jobject,
+    fd: jobject,
+) -> jobject {
+    rethrow_java_io_exception(env, || {
+        let info = get_socket_info(env, fd)?;
+        if info.socket_type != SocketType::Datagram || info.family == SocketFamily::Unix {
+            return Err("not an inet datagram socket".into());
+        }
+
+        // DatagramChannelImpl takes the family of an adopted descriptor as inet6 whenever ipv6 is available
+        if info.family == SocketFamily::Inet
+            && jcall!(env, CallStaticBooleanMethod, *C_NET.get(), *M_NET_IS_IPV6_AVAILABLE.get()) != JNI_FALSE
+        {
+            return Err("ipv4 datagram socket is unsupported while ipv6 is available".into());
+        }
+
+        Ok(jcall!(
+            env,
+            NewObject,
+            *C_DATAGRAM_CHANNEL_IMPL.get(),
+            *M_NEW_DATAGRAM_CHANNEL_IMPL.get(),
+            sp,
+            fd
+        ))
+    })
+    .unwrap_or(null_mut())
+}
+
+#[cfg(target_os = "linux")]
+static C_SOURCE_CHANNEL_IMPL: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/SourceChannelImpl"));
+#[cfg(target_os = "linux")]
+static M_NEW_SOURCE_CHANNEL_IMPL: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_SOURCE_CHANNEL_IMPL,
+        "<init>",
+        "(Ljava/nio/channels/spi/SelectorProvider;Ljava/io/FileDescriptor;)V",
+    ))
+});
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeNewPipeSourceChannel(
+    env: *mut JNIEnv,
+    _: jclass,
+    sp: jobject,
+    fd: jobject,
+) -> jobject {
+    #[cfg(windows)]
+    return {
+        let _ = (sp, fd);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        crate::linux::file::check_pipe(get_file_descriptor(env, fd), false)?;
+
+        Ok(jcall!(
+            env,
+            NewObject,
+            *C_SOURCE_CHANNEL_IMPL.get(),
+            *M_NEW_SOURCE_CHANNEL_IMPL.get(),
+            sp,
+            fd
+        ))
+    })
+    .unwrap_or(null_mut());
+}
+
+#[cfg(target_os = "linux")]
+static C_SINK_CHANNEL_IMPL: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("sun/nio/ch/SinkChannelImpl"));
+#[cfg(target_os = "linux")]
+static M_NEW_SINK_CHANNEL_IMPL: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_SINK_CHANNEL_IMPL,
+        "<init>",
+        "(Ljava/nio/channels/spi/SelectorProvider;Ljava/io/FileDescriptor;)V",
+    ))
+});
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_FileCompat_nativeNewPipeSinkChannel(
+    env: *mut JNIEnv,
+    _: jclass,
+    sp: jobject,
+    fd: jobject,
+) -> jobject {
+    #[cfg(windows)]
+    return {
+        let _ = (sp, fd);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        crate::linux::file::check_pipe(get_file_descriptor(env, fd), true)?;
+
+        Ok(jcall!(
+            env,
+            NewObject,
+            *C_SINK_CHANNEL_IMPL.get(),
+            *M_NEW_SINK_CHANNEL_IMPL.get(),
+            sp,
+            fd
+        ))
+    })
+    .unwrap_or(null_mut());
+}
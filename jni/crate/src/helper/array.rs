@@ -1,8 +1,12 @@
-use std::iter;
+use std::{iter, ptr::null_mut};
 
-use jni_sys::{jbyteArray, jobject, jobjectArray, JNIEnv};
+use jni_sys::{jbyteArray, jclass, jint, jintArray, jobject, jobjectArray, jsize, JNIEnv};
 
-use crate::helper::call::jcall;
+use crate::helper::{
+    call::jcall,
+    lazy::{JRef, LazyJRef},
+    strings::string_to_java_string,
+};
 
 pub fn collect_java_bytes(env: *mut JNIEnv, array: jbyteArray) -> Vec<u8> {
     let length = jcall!(env, GetArrayLength, array);
@@ -13,6 +17,15 @@ pub fn collect_java_bytes(env: *mut JNIEnv, array: jbyteArray) -> Vec<u8> {
     ret
 }
 
+pub fn collect_java_ints(env: *mut JNIEnv, array: jintArray) -> Vec<jint> {
+    let length = jcall!(env, GetArrayLength, array);
+    let mut ret = vec![0 as jint; length as usize];
+
+    jcall!(env, GetIntArrayRegion, array, 0, length, ret.as_mut_ptr());
+
+    ret
+}
+
 pub fn iterate_object_array(env: *mut JNIEnv, array: jobjectArray) -> impl Iterator<Item = jobject> {
     let mut index = 0;
     let length = jcall!(env, GetArrayLength, array);
@@ -29,3 +42,47 @@ pub fn iterate_object_array(env: *mut JNIEnv, array: jobjectArray) -> impl Itera
         }
     })
 }
+
+static C_STRING: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("java/lang/String"));
+
+pub fn new_java_object_array(env: *mut JNIEnv, class: jclass, objects: &[jobject]) -> jobjectArray {
+    let array = jcall!(env, NewObjectArray, objects.len() as jsize, class, null_mut());
+
+    for (index, object) in objects.iter().enumerate() {
+        jcall!(env, SetObjectArrayElement, array, index as jsize, *object);
+    }
+
+    array
+}
+
+pub fn new_java_string_array<S: AsRef<str>>(env: *mut JNIEnv, strings: &[S]) -> jobjectArray {
+    let strings = strings
+        .iter()
+        .map(|s| string_to_java_string(env, s.as_ref()))
+        .collect::<Vec<_>>();
+
+    new_java_object_array(env, *C_STRING.get(), &strings)
+}
+
+pub fn new_java_byte_array(env: *mut JNIEnv, values: &[u8]) -> jbyteArray {
+    let array = jcall!(env, NewByteArray, values.len() as jsize);
+
+    jcall!(
+        env,
+        SetByteArrayRegion,
+        array,
+        0,
+        values.len() as jsize,
+        values.as_ptr().cast()
+    );
+
+    array
+}
+
+pub fn new_java_int_array(env: *mut JNIEnv, values: &[jint]) -> jintArray {
+    let array = jcall!(env, NewIntArray, values.len() as jsize);
+
+    jcall!(env, SetIntArrayRegion, array, 0, values.len() as jsize, values.as_ptr());
+
+    array
+}
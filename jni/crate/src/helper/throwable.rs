@@ -31,3 +31,10 @@ pub fn rethrow_java_io_exception<R, F: FnOnce() -> Result<R, Box<dyn Error>>>(en
         }
     }
 }
+
+#[cfg(windows)]
+pub fn throw_unsupported_operation(env: *mut JNIEnv) {
+    let clazz = jcall!(env, FindClass, cstr::cstr!("java/lang/UnsupportedOperationException").as_ptr());
+
+    jcall!(env, ThrowNew, clazz, cstr::cstr!("Unsupported platform").as_ptr());
+}
@@ -13,6 +13,7 @@ mod process;
 mod security;
 mod shell;
 mod theme;
+mod watcher;
 mod window;
 
 mod helper;
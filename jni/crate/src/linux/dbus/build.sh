@@ -8,3 +8,7 @@ zbus-xmlgen xml/org.freedesktop.portal.FileChooser.xml > file_chooser.rs
 zbus-xmlgen xml/org.freedesktop.portal.OpenURI.xml > open_uri.rs
 zbus-xmlgen xml/org.freedesktop.portal.Notification.xml > notifications.rs
 zbus-xmlgen xml/org.kde.StatusNotifierWatcher.xml > notifier_watcher.rs
+zbus-xmlgen xml/ca.desrt.dconf.Writer.xml > dconf_writer.rs
+zbus-xmlgen xml/org.freedesktop.systemd1.Manager.xml > systemd_manager.rs
+zbus-xmlgen xml/org.freedesktop.resolve1.Manager.xml > resolve1_manager.rs
+zbus-xmlgen xml/org.freedesktop.NetworkManager.xml > network_manager.rs
@@ -0,0 +1,30 @@
+//! # DBus interface proxy for: `ca.desrt.dconf.Writer`
+//!
+//! This code was generated by `zbus-xmlgen` `3.1.0` from DBus introspection data.
+//! Source: `ca.desrt.dconf.Writer.xml`.
+//!
+//! You may prefer to adapt it, instead of using it verbatim.
+//!
+//! More information can be found in the
+//! [Writing a client proxy](https://dbus.pages.freedesktop.org/zbus/client.html)
+//! section of the zbus documentation.
+//!
+
+use zbus::dbus_proxy;
+
+#[dbus_proxy(interface = "ca.desrt.dconf.Writer", assume_defaults = true)]
+trait Writer {
+    /// Change method
+    fn change(&self, blob: &[u8]) -> zbus::Result<String>;
+
+    /// Init method
+    fn init(&self) -> zbus::Result<()>;
+
+    /// Notify signal
+    #[dbus_proxy(signal)]
+    fn notify(&self, prefix: &str, changes: Vec<&str>, tag: &str) -> zbus::Result<()>;
+
+    /// WritabilityNotify signal
+    #[dbus_proxy(signal)]
+    fn writability_notify(&self, path: &str) -> zbus::Result<()>;
+}
@@ -1,8 +1,12 @@
 pub mod dbus_menu;
+pub mod dconf_writer;
 pub mod file_chooser;
+pub mod network_manager;
 pub mod notifications;
 pub mod notifier_item;
 pub mod notifier_watcher;
 pub mod open_uri;
 pub mod request;
+pub mod resolve1_manager;
 pub mod settings;
+pub mod systemd_manager;
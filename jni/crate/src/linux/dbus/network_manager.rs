@@ -0,0 +1,87 @@
+//! # DBus interface proxies for: `org.freedesktop.NetworkManager`, `org.freedesktop.NetworkManager.Connection.Active`, `org.freedesktop.NetworkManager.Device`, `org.freedesktop.NetworkManager.Settings.Connection`
+//!
+//! This code was generated by `zbus-xmlgen` `3.1.0` from DBus introspection data.
+//! Source: `org.freedesktop.NetworkManager.xml`.
+//!
+//! You may prefer to adapt it, instead of using it verbatim.
+//!
+//! More information can be found in the
+//! [Writing a client proxy](https://dbus.pages.freedesktop.org/zbus/client.html)
+//! section of the zbus documentation.
+//!
+
+use zbus::dbus_proxy;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    /// ActivateConnection method
+    fn activate_connection(
+        &self,
+        connection: &zbus::zvariant::ObjectPath<'_>,
+        device: &zbus::zvariant::ObjectPath<'_>,
+        specific_object: &zbus::zvariant::ObjectPath<'_>,
+    ) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+
+    /// PrimaryConnection property
+    #[dbus_proxy(property)]
+    fn primary_connection(&self) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Connection.Active",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Active {
+    /// Connection property
+    #[dbus_proxy(property)]
+    fn connection(&self) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+
+    /// Devices property
+    #[dbus_proxy(property)]
+    fn devices(&self) -> zbus::Result<Vec<zbus::zvariant::OwnedObjectPath>>;
+
+    /// SpecificObject property
+    #[dbus_proxy(property)]
+    fn specific_object(&self) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Device",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Device {
+    /// Reapply method
+    fn reapply(
+        &self,
+        connection: std::collections::HashMap<&str, std::collections::HashMap<&str, zbus::zvariant::Value<'_>>>,
+        version_id: u64,
+        flags: u32,
+    ) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Connection {
+    /// GetSettings method
+    fn get_settings(
+        &self,
+    ) -> zbus::Result<std::collections::HashMap<String, std::collections::HashMap<String, zbus::zvariant::OwnedValue>>>;
+
+    /// Update2 method
+    fn update2(
+        &self,
+        settings: std::collections::HashMap<&str, std::collections::HashMap<&str, zbus::zvariant::Value<'_>>>,
+        flags: u32,
+        args: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
+    ) -> zbus::Result<std::collections::HashMap<String, zbus::zvariant::OwnedValue>>;
+
+    /// Updated signal
+    #[dbus_proxy(signal)]
+    fn updated(&self) -> zbus::Result<()>;
+}
@@ -0,0 +1,33 @@
+//! # DBus interface proxy for: `org.freedesktop.resolve1.Manager`
+//!
+//! This code was generated by `zbus-xmlgen` `3.1.0` from DBus introspection data.
+//! Source: `org.freedesktop.resolve1.Manager.xml`.
+//!
+//! You may prefer to adapt it, instead of using it verbatim.
+//!
+//! More information can be found in the
+//! [Writing a client proxy](https://dbus.pages.freedesktop.org/zbus/client.html)
+//! section of the zbus documentation.
+//!
+
+use zbus::dbus_proxy;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.resolve1.Manager",
+    default_service = "org.freedesktop.resolve1",
+    default_path = "/org/freedesktop/resolve1"
+)]
+trait Manager {
+    /// RevertLink method
+    fn revert_link(&self, ifindex: i32) -> zbus::Result<()>;
+
+    /// SetLinkDNS method
+    #[dbus_proxy(name = "SetLinkDNS")]
+    fn set_link_dns(&self, ifindex: i32, addresses: &[&(i32, &[u8])]) -> zbus::Result<()>;
+
+    /// SetLinkDefaultRoute method
+    fn set_link_default_route(&self, ifindex: i32, enable: bool) -> zbus::Result<()>;
+
+    /// SetLinkDomains method
+    fn set_link_domains(&self, ifindex: i32, domains: &[&(&str, bool)]) -> zbus::Result<()>;
+}
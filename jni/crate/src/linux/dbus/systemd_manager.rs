@@ -0,0 +1,33 @@
+//! # DBus interface proxy for: `org.freedesktop.systemd1.Manager`
+//!
+//! This code was generated by `zbus-xmlgen` `3.1.0` from DBus introspection data.
+//! Source: `org.freedesktop.systemd1.Manager.xml`.
+//!
+//! You may prefer to adapt it, instead of using it verbatim.
+//!
+//! More information can be found in the
+//! [Writing a client proxy](https://dbus.pages.freedesktop.org/zbus/client.html)
+//! section of the zbus documentation.
+//!
+
+use zbus::dbus_proxy;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    /// SetEnvironment method
+    fn set_environment(&self, assignments: &[&str]) -> zbus::Result<()>;
+
+    /// UnsetAndSetEnvironment method
+    fn unset_and_set_environment(&self, names: &[&str], assignments: &[&str]) -> zbus::Result<()>;
+
+    /// UnsetEnvironment method
+    fn unset_environment(&self, names: &[&str]) -> zbus::Result<()>;
+
+    /// Environment property
+    #[dbus_proxy(property)]
+    fn environment(&self) -> zbus::Result<Vec<String>>;
+}
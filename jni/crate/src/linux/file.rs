@@ -1,8 +1,46 @@
-use std::{error::Error, os::fd::RawFd};
+use std::{
+    error::Error,
+    ffi::{CStr, CString, OsStr},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    mem,
+    mem::size_of,
+    os::{
+        fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+        unix::{
+            ffi::OsStrExt,
+            fs::{MetadataExt, OpenOptionsExt},
+        },
+    },
+    path::{Path, PathBuf},
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    thread,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
-use libc::{fcntl, pipe2, socketpair, AF_UNIX, FD_CLOEXEC, F_GETFD, F_SETFD, O_CLOEXEC, SOCK_STREAM};
+use errno::{errno, Errno};
 
-use crate::{common::file::FileDescriptor, linux::errno::syscall, utils::scoped::Scoped};
+use libc::{
+    c_int, c_short, c_void, copy_file_range, eventfd, fchmod, fchown, fcntl, flock, fstat, fstatat, fsync, geteuid, getsockopt,
+    linkat, memfd_create, mkdirat, mode_t, openat, pipe2, poll, pollfd, read, renameat, renameat2, sendfile, socketpair,
+    socklen_t, splice, unlinkat, write, AF_INET, AF_INET6, AF_UNIX, AT_REMOVEDIR, EACCES, EAGAIN, EEXIST, EFD_CLOEXEC,
+    EFD_NONBLOCK, EINTR, EINVAL, ENOENT, ENOSYS, EOPNOTSUPP, EPIPE, EXDEV, FD_CLOEXEC, F_ADD_SEALS, F_GETFD, F_GETFL,
+    F_OFD_SETLK, F_OFD_SETLKW, F_RDLCK, F_SEAL_GROW, F_SEAL_SHRINK, F_SEAL_WRITE, F_SETFD, F_SETPIPE_SZ, F_UNLCK, F_WRLCK,
+    MFD_ALLOW_SEALING, MFD_CLOEXEC, O_ACCMODE, O_CLOEXEC, O_CREAT, O_DIRECTORY, O_EXCL, O_NOFOLLOW, O_NONBLOCK, O_RDONLY, O_RDWR,
+    O_WRONLY, POLLIN, POLLOUT, RENAME_EXCHANGE, SEEK_SET, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET, SO_ACCEPTCONN, SO_DOMAIN, SO_TYPE,
+    SPLICE_F_MOVE, SPLICE_F_NONBLOCK, S_IFDIR, S_IFIFO, S_IFMT, S_IFREG,
+};
+
+use crate::{
+    common::file::{FileDescriptor, ForwardListener, Forwarder, PrivateDirectory, SocketFamily, SocketInfo, SocketType},
+    linux::errno::syscall,
+    utils::scoped::Scoped,
+};
 
 pub fn set_file_descriptor_inheritable(fd: FileDescriptor, inheritable: bool) -> Result<(), Box<dyn Error>> {
     unsafe {
@@ -51,3 +89,1033 @@ pub fn create_pipe() -> Result<(FileDescriptor, FileDescriptor), Box<dyn Error>>
 
     Ok((rx.swap(-1) as FileDescriptor, tx.swap(-1) as FileDescriptor))
 }
+
+fn get_socket_option(fd: FileDescriptor, option: c_int) -> Result<c_int, Errno> {
+    let mut value: c_int = 0;
+    let mut length = size_of::<c_int>() as socklen_t;
+
+    unsafe {
+        syscall(|| {
+            getsockopt(
+                fd as RawFd,
+                SOL_SOCKET,
+                option,
+                (&mut value as *mut c_int).cast(),
+                &mut length,
+            )
+        })?;
+    }
+
+    Ok(value)
+}
+
+pub fn get_socket_info(fd: FileDescriptor) -> Result<SocketInfo, Box<dyn Error>> {
+    let family = match get_socket_option(fd, SO_DOMAIN)? {
+        AF_INET => SocketFamily::Inet,
+        AF_INET6 => SocketFamily::Inet6,
+        AF_UNIX => SocketFamily::Unix,
+        family => return Err(format!("unsupported socket family: {family}").into()),
+    };
+
+    let socket_type = match get_socket_option(fd, SO_TYPE)? {
+        SOCK_STREAM => SocketType::Stream,
+        SOCK_DGRAM => SocketType::Datagram,
+        socket_type => return Err(format!("unsupported socket type: {socket_type}").into()),
+    };
+
+    Ok(SocketInfo {
+        family,
+        socket_type,
+        listening: get_socket_option(fd, SO_ACCEPTCONN)? != 0,
+    })
+}
+
+// Channels of a pipe trust the descriptor blindly, a regular file or the wrong end would only fail on first use
+pub fn check_pipe(fd: FileDescriptor, writable: bool) -> Result<(), Box<dyn Error>> {
+    let mut stat: libc::stat = unsafe { mem::zeroed() };
+
+    let flags = unsafe {
+        syscall(|| fstat(fd as RawFd, &mut stat))?;
+        syscall(|| fcntl(fd as RawFd, F_GETFL))?
+    };
+
+    if stat.st_mode & S_IFMT != S_IFIFO {
+        return Err("not a pipe".into());
+    }
+
+    let expected = if writable { O_WRONLY } else { O_RDONLY };
+    let mode = flags & O_ACCMODE;
+    if mode != expected && mode != O_RDWR {
+        return Err(format!("pipe is not {}", if writable { "writable" } else { "readable" }).into());
+    }
+
+    Ok(())
+}
+
+pub fn create_sealed_file(name: &str, content: &[u8]) -> Result<FileDescriptor, Box<dyn Error>> {
+    let name = CString::new(name)?;
+
+    let mut file = unsafe {
+        let fd = syscall(|| memfd_create(name.as_ptr(), MFD_CLOEXEC | MFD_ALLOW_SEALING))?;
+
+        File::from_raw_fd(fd)
+    };
+
+    file.write_all(content)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    unsafe {
+        syscall(|| fcntl(file.as_raw_fd(), F_ADD_SEALS, F_SEAL_WRITE | F_SEAL_SHRINK | F_SEAL_GROW))?;
+    }
+
+    Ok(file.into_raw_fd() as FileDescriptor)
+}
+
+fn set_ofd_lock(fd: FileDescriptor, lock_type: i32, wait: bool) -> Result<bool, Box<dyn Error>> {
+    let mut lock: flock = unsafe { mem::zeroed() };
+
+    lock.l_type = lock_type as _;
+    lock.l_whence = SEEK_SET as _;
+
+    let command = if wait { F_OFD_SETLKW } else { F_OFD_SETLK };
+
+    loop {
+        return match unsafe { syscall(|| fcntl(fd as RawFd, command, &mut lock as *mut flock)) } {
+            Ok(_) => Ok(true),
+            Err(err) if err.0 == EINTR => continue,
+            Err(err) if !wait && (err.0 == EAGAIN || err.0 == EACCES) => Ok(false),
+            Err(err) => Err(err.into()),
+        };
+    }
+}
+
+pub fn lock_file(fd: FileDescriptor, exclusive: bool, timeout: Option<Duration>) -> Result<bool, Box<dyn Error>> {
+    let lock_type = if exclusive { F_WRLCK } else { F_RDLCK };
+
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return set_ofd_lock(fd, lock_type, true),
+    };
+
+    let deadline = Instant::now() + timeout;
+    let mut backoff = Duration::from_millis(1);
+
+    loop {
+        if set_ofd_lock(fd, lock_type, false)? {
+            return Ok(true);
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(false);
+        }
+
+        thread::sleep(backoff.min(deadline - now));
+
+        backoff = (backoff * 2).min(Duration::from_millis(50));
+    }
+}
+
+pub fn unlock_file(fd: FileDescriptor) -> Result<(), Box<dyn Error>> {
+    set_ofd_lock(fd, F_UNLCK, false)?;
+
+    Ok(())
+}
+
+fn parent_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn sync_parent_directory(path: &Path) -> Result<(), Box<dyn Error>> {
+    let dir = OpenOptions::new()
+        .read(true)
+        .custom_flags(O_DIRECTORY)
+        .open(parent_of(path))?;
+
+    unsafe {
+        syscall(|| fsync(dir.as_raw_fd()))?;
+    }
+
+    Ok(())
+}
+
+// Backup on another filesystem can neither be renamed nor linked into place, copy it with the metadata of the original
+fn copy_to_backup(dir_fd: RawFd, name: &CStr, backup: &Path) -> Result<(), Box<dyn Error>> {
+    let mut source = unsafe { File::from_raw_fd(syscall(|| openat(dir_fd, name.as_ptr(), O_RDONLY | O_CLOEXEC))?) };
+
+    let mut metadata: libc::stat = unsafe { mem::zeroed() };
+    unsafe {
+        syscall(|| fstat(source.as_raw_fd(), &mut metadata))?;
+    }
+
+    let mut content = Vec::new();
+    source.read_to_end(&mut content)?;
+
+    replace_file(backup, &content, None, Some(metadata))
+}
+
+fn move_to_backup(dir_fd: RawFd, name: &CStr, backup: &Path) -> Result<(), Box<dyn Error>> {
+    let backup_name = CString::new(backup.as_os_str().as_bytes())?;
+
+    match unsafe { syscall(|| renameat(dir_fd, name.as_ptr(), libc::AT_FDCWD, backup_name.as_ptr())) } {
+        Ok(_) => sync_parent_directory(backup),
+        Err(err) if err.0 == EXDEV => {
+            copy_to_backup(dir_fd, name, backup)?;
+
+            unsafe {
+                syscall(|| unlinkat(dir_fd, name.as_ptr(), 0))?;
+            }
+
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn link_to_backup(dir_fd: RawFd, name: &CStr, backup: &Path) -> Result<(), Box<dyn Error>> {
+    let backup_name = CString::new(backup.as_os_str().as_bytes())?;
+
+    unsafe {
+        syscall(|| unlinkat(libc::AT_FDCWD, backup_name.as_ptr(), 0)).ok();
+    }
+
+    match unsafe { syscall(|| linkat(dir_fd, name.as_ptr(), libc::AT_FDCWD, backup_name.as_ptr(), 0)) } {
+        Ok(_) => sync_parent_directory(backup),
+        Err(err) if err.0 == EXDEV => copy_to_backup(dir_fd, name, backup),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn replace_file(path: &Path, content: &[u8], backup: Option<&Path>, metadata: Option<libc::stat>) -> Result<(), Box<dyn Error>> {
+    let parent = parent_of(path);
+    let name = CString::new(path.file_name().ok_or("invalid file name")?.as_bytes())?;
+    let temp_name = CString::new(format!(".{}.{:016x}.tmp", name.to_string_lossy(), rand::random::<u64>()))?;
+
+    let dir = OpenOptions::new().read(true).custom_flags(O_DIRECTORY).open(parent)?;
+
+    let mut original: libc::stat = unsafe { mem::zeroed() };
+    let exists = match unsafe { syscall(|| fstatat(dir.as_raw_fd(), name.as_ptr(), &mut original, 0)) } {
+        Ok(_) => true,
+        Err(err) if err.0 == ENOENT => false,
+        Err(err) => return Err(err.into()),
+    };
+    let metadata = metadata.or(Some(original).filter(|_| exists));
+
+    // Content may be private, keep it owner only until permissions of the original are applied
+    let mut temp = unsafe {
+        let fd = syscall(|| {
+            openat(
+                dir.as_raw_fd(),
+                temp_name.as_ptr(),
+                O_WRONLY | O_CREAT | O_EXCL | O_CLOEXEC,
+                if metadata.is_some() { 0o600 } else { 0o666 },
+            )
+        })?;
+
+        File::from_raw_fd(fd)
+    };
+
+    let mut cleanup = Scoped::new(Some(&temp_name), |name| {
+        if let Some(name) = name {
+            unsafe {
+                unlinkat(dir.as_raw_fd(), name.as_ptr(), 0);
+            }
+        }
+    });
+
+    if let Some(metadata) = &metadata {
+        let mut current: libc::stat = unsafe { mem::zeroed() };
+
+        unsafe {
+            syscall(|| fstat(temp.as_raw_fd(), &mut current))?;
+
+            if current.st_uid != metadata.st_uid || current.st_gid != metadata.st_gid {
+                syscall(|| fchown(temp.as_raw_fd(), metadata.st_uid, metadata.st_gid))?;
+            }
+
+            syscall(|| fchmod(temp.as_raw_fd(), metadata.st_mode & 0o7777))?;
+        }
+    }
+
+    temp.write_all(content)?;
+
+    unsafe {
+        syscall(|| fsync(temp.as_raw_fd()))?;
+    }
+
+    drop(temp);
+
+    let dir_fd = dir.as_raw_fd();
+
+    match backup {
+        Some(backup) if exists => {
+            let exchanged = unsafe { syscall(|| renameat2(dir_fd, temp_name.as_ptr(), dir_fd, name.as_ptr(), RENAME_EXCHANGE)) };
+            match exchanged {
+                Ok(_) => {
+                    // Temporary name holds the original from now on, it must never be unlinked
+                    cleanup.swap(None);
+
+                    unsafe {
+                        syscall(|| fsync(dir_fd))?;
+                    }
+
+                    move_to_backup(dir_fd, &temp_name, backup).map_err(|err| {
+                        let kept = parent.join(OsStr::from_bytes(temp_name.as_bytes()));
+
+                        format!("unable to create backup, original kept at {}: {err}", kept.display())
+                    })?;
+                }
+                Err(err) if err.0 == EINVAL => unsafe {
+                    // Filesystem without RENAME_EXCHANGE support, keep the original reachable through a hard link instead.
+                    link_to_backup(dir_fd, &name, backup)?;
+                    syscall(|| renameat(dir_fd, temp_name.as_ptr(), dir_fd, name.as_ptr()))?;
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+        _ => unsafe {
+            syscall(|| renameat2(dir_fd, temp_name.as_ptr(), dir_fd, name.as_ptr(), 0))?;
+        },
+    }
+
+    cleanup.swap(None);
+
+    unsafe {
+        syscall(|| fsync(dir_fd))?;
+    }
+
+    Ok(())
+}
+
+pub fn write_file_atomically(path: &Path, content: &[u8], backup: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    replace_file(path, content, backup, None)
+}
+
+fn select_private_directory_base() -> PathBuf {
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        if let Ok(metadata) = std::fs::symlink_metadata(&runtime_dir) {
+            if runtime_dir.is_absolute()
+                && metadata.is_dir()
+                && metadata.uid() == unsafe { geteuid() }
+                && metadata.mode() & 0o077 == 0
+            {
+                return runtime_dir;
+            }
+        }
+    }
+
+    PathBuf::from("/tmp")
+}
+
+struct PrivateDirectoryImpl {
+    path: String,
+    fd: Scoped<c_int>,
+}
+
+impl PrivateDirectory for PrivateDirectoryImpl {
+    fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for PrivateDirectoryImpl {
+    fn drop(&mut self) {
+        let mut opened: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { syscall(|| fstat(*self.fd, &mut opened)) }.is_err() {
+            return;
+        }
+
+        // Only remove the tree if the path still refers to the directory we created.
+        match std::fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.is_dir() && metadata.dev() == opened.st_dev && metadata.ino() == opened.st_ino => {
+                std::fs::remove_dir_all(&self.path).ok();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn create_private_directory(prefix: &str) -> Result<Box<dyn PrivateDirectory>, Box<dyn Error>> {
+    if prefix.is_empty() || prefix.contains('/') || prefix == "." || prefix == ".." {
+        return Err(format!("invalid directory prefix: {prefix}").into());
+    }
+
+    let base = select_private_directory_base();
+    let base_dir = OpenOptions::new().read(true).custom_flags(O_DIRECTORY).open(&base)?;
+
+    loop {
+        let name = format!("{prefix}-{:016x}", rand::random::<u64>());
+        let c_name = CString::new(name.as_str())?;
+
+        match unsafe { syscall(|| mkdirat(base_dir.as_raw_fd(), c_name.as_ptr(), 0o700)) } {
+            Ok(_) => {}
+            Err(err) if err.0 == EEXIST => continue,
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut cleanup = Scoped::new(Some(&c_name), |name| {
+            if let Some(name) = name {
+                unsafe {
+                    unlinkat(base_dir.as_raw_fd(), name.as_ptr(), AT_REMOVEDIR);
+                }
+            }
+        });
+
+        let fd = Scoped::new_fd(unsafe {
+            syscall(|| {
+                openat(
+                    base_dir.as_raw_fd(),
+                    c_name.as_ptr(),
+                    O_RDONLY | O_DIRECTORY | O_NOFOLLOW | O_CLOEXEC,
+                )
+            })?
+        });
+
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        unsafe {
+            syscall(|| fstat(*fd, &mut stat))?;
+        }
+
+        if stat.st_mode & S_IFMT != S_IFDIR || stat.st_uid != unsafe { geteuid() } {
+            return Err(format!("{} is not owned by current user", base.join(&name).display()).into());
+        }
+
+        unsafe {
+            syscall(|| fchmod(*fd, 0o700))?;
+        }
+
+        let path = base.join(&name).to_str().ok_or("invalid path")?.to_owned();
+
+        cleanup.swap(None);
+
+        return Ok(Box::new(PrivateDirectoryImpl { path, fd }));
+    }
+}
+
+const FORWARD_CHUNK_SIZE: usize = 1 << 20;
+const FORWARD_BUFFER_SIZE: usize = 1 << 16;
+const DEFAULT_PIPE_CAPACITY: usize = 1 << 16;
+const MAX_PIPE_CAPACITY: usize = 1 << 20;
+
+// Privileged processes may grow pipes beyond pipe-max-size, capacity comes from callers and is never trusted
+fn clamp_pipe_capacity(capacity: usize) -> usize {
+    if capacity == 0 {
+        return DEFAULT_PIPE_CAPACITY;
+    }
+
+    let max = std::fs::read_to_string("/proc/sys/fs/pipe-max-size")
+        .ok()
+        .and_then(|max| max.trim().parse::<usize>().ok())
+        .unwrap_or(MAX_PIPE_CAPACITY)
+        .min(MAX_PIPE_CAPACITY);
+
+    capacity.min(max)
+}
+
+enum Transferred {
+    Bytes(usize),
+    Again,
+    Eof,
+}
+
+struct Forwarding {
+    source: RawFd,
+    target: RawFd,
+    pipe_capacity: usize,
+    stop_fd: RawFd,
+    transferred: Arc<AtomicU64>,
+}
+
+impl Forwarding {
+    fn file_type(fd: RawFd) -> Result<mode_t, Errno> {
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+
+        unsafe { syscall(|| fstat(fd, &mut stat))? };
+
+        Ok(stat.st_mode & S_IFMT)
+    }
+
+    fn wait(&self, fd: RawFd, events: c_short) -> Result<bool, Errno> {
+        let mut fds = [
+            pollfd { fd, events, revents: 0 },
+            pollfd {
+                fd: self.stop_fd,
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+
+        loop {
+            match unsafe { syscall(|| poll(fds.as_mut_ptr(), fds.len() as _, -1)) } {
+                Ok(_) => return Ok(fds[1].revents == 0),
+                Err(err) if err.0 == EINTR => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        let mut fds = [pollfd {
+            fd: self.stop_fd,
+            events: POLLIN,
+            revents: 0,
+        }];
+
+        unsafe { poll(fds.as_mut_ptr(), fds.len() as _, 0) != 0 }
+    }
+
+    fn transfer(result: isize) -> Result<Transferred, Errno> {
+        if result > 0 {
+            return Ok(Transferred::Bytes(result as usize));
+        } else if result == 0 {
+            return Ok(Transferred::Eof);
+        }
+
+        match errno() {
+            err if err.0 == EINTR || err.0 == EAGAIN => Ok(Transferred::Again),
+            err => Err(err),
+        }
+    }
+
+    fn add_transferred(&self, bytes: usize) {
+        self.transferred.fetch_add(bytes as u64, Relaxed);
+    }
+
+    fn copy_file(&self, target_is_file: bool) -> Result<(), Errno> {
+        let mut use_copy_file_range = target_is_file;
+
+        while !self.is_stopped() {
+            let result = if use_copy_file_range {
+                unsafe { copy_file_range(self.source, null_mut(), self.target, null_mut(), FORWARD_CHUNK_SIZE, 0) }
+            } else {
+                if !self.wait(self.target, POLLOUT)? {
+                    return Ok(());
+                }
+
+                unsafe { sendfile(self.target, self.source, null_mut(), FORWARD_CHUNK_SIZE) }
+            };
+
+            match Self::transfer(result) {
+                Ok(Transferred::Bytes(bytes)) => self.add_transferred(bytes),
+                Ok(Transferred::Again) => continue,
+                Ok(Transferred::Eof) => return Ok(()),
+                Err(err) if use_copy_file_range && [EXDEV, EINVAL, ENOSYS, EOPNOTSUPP].contains(&err.0) => {
+                    use_copy_file_range = false;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn splice_direct(&self) -> Result<(), Errno> {
+        loop {
+            if !self.wait(self.source, POLLIN)? || !self.wait(self.target, POLLOUT)? {
+                return Ok(());
+            }
+
+            let result = unsafe {
+                splice(
+                    self.source,
+                    null_mut(),
+                    self.target,
+                    null_mut(),
+                    self.pipe_capacity,
+                    SPLICE_F_MOVE | SPLICE_F_NONBLOCK,
+                )
+            };
+
+            match Self::transfer(result)? {
+                Transferred::Bytes(bytes) => self.add_transferred(bytes),
+                Transferred::Again => continue,
+                Transferred::Eof => return Ok(()),
+            }
+        }
+    }
+
+    fn splice_through_pipe(&self, reader: RawFd, writer: RawFd) -> Result<(), Errno> {
+        loop {
+            if !self.wait(self.source, POLLIN)? {
+                return Ok(());
+            }
+
+            let result = unsafe {
+                splice(
+                    self.source,
+                    null_mut(),
+                    writer,
+                    null_mut(),
+                    self.pipe_capacity,
+                    SPLICE_F_MOVE | SPLICE_F_NONBLOCK,
+                )
+            };
+
+            let mut pending = match Self::transfer(result)? {
+                Transferred::Bytes(bytes) => bytes,
+                Transferred::Again => continue,
+                Transferred::Eof => return Ok(()),
+            };
+
+            while pending > 0 {
+                if !self.wait(self.target, POLLOUT)? {
+                    return Ok(());
+                }
+
+                let result = unsafe {
+                    splice(
+                        reader,
+                        null_mut(),
+                        self.target,
+                        null_mut(),
+                        pending,
+                        SPLICE_F_MOVE | SPLICE_F_NONBLOCK,
+                    )
+                };
+
+                match Self::transfer(result)? {
+                    Transferred::Bytes(bytes) => {
+                        pending -= bytes;
+
+                        self.add_transferred(bytes);
+                    }
+                    Transferred::Again => continue,
+                    Transferred::Eof => return Err(Errno(EPIPE)),
+                }
+            }
+        }
+    }
+
+    fn copy_buffered(&self) -> Result<(), Errno> {
+        let mut buffer = vec![0u8; FORWARD_BUFFER_SIZE];
+
+        loop {
+            if !self.wait(self.source, POLLIN)? {
+                return Ok(());
+            }
+
+            let length = match Self::transfer(unsafe { read(self.source, buffer.as_mut_ptr().cast(), buffer.len()) })? {
+                Transferred::Bytes(bytes) => bytes,
+                Transferred::Again => continue,
+                Transferred::Eof => return Ok(()),
+            };
+
+            let mut offset = 0;
+            while offset < length {
+                if !self.wait(self.target, POLLOUT)? {
+                    return Ok(());
+                }
+
+                let result = unsafe { write(self.target, buffer[offset..].as_ptr().cast(), length - offset) };
+
+                match Self::transfer(result)? {
+                    Transferred::Bytes(bytes) => {
+                        offset += bytes;
+
+                        self.add_transferred(bytes);
+                    }
+                    Transferred::Again => continue,
+                    Transferred::Eof => return Err(Errno(EPIPE)),
+                }
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Errno> {
+        let source_type = Self::file_type(self.source)?;
+        let target_type = Self::file_type(self.target)?;
+
+        if source_type == S_IFREG {
+            return self.copy_file(target_type == S_IFREG);
+        }
+
+        if source_type == S_IFIFO || target_type == S_IFIFO {
+            let pipe = if source_type == S_IFIFO { self.source } else { self.target };
+
+            if let Ok(capacity) = unsafe { syscall(|| fcntl(pipe, F_SETPIPE_SZ, self.pipe_capacity as c_int)) } {
+                self.pipe_capacity = capacity as usize;
+            }
+
+            return match self.splice_direct() {
+                Err(err) if err.0 == EINVAL && self.transferred.load(Relaxed) == 0 => self.copy_buffered(),
+                result => result,
+            };
+        }
+
+        let mut pipe = [-1; 2];
+
+        unsafe { syscall(|| pipe2(pipe.as_mut_ptr(), O_CLOEXEC | O_NONBLOCK))? };
+
+        let reader = Scoped::new_fd(pipe[0]);
+        let writer = Scoped::new_fd(pipe[1]);
+
+        if let Ok(capacity) = unsafe { syscall(|| fcntl(*writer, F_SETPIPE_SZ, self.pipe_capacity as c_int)) } {
+            self.pipe_capacity = capacity as usize;
+        }
+
+        match self.splice_through_pipe(*reader, *writer) {
+            Err(err) if err.0 == EINVAL && self.transferred.load(Relaxed) == 0 => self.copy_buffered(),
+            result => result,
+        }
+    }
+}
+
+struct ForwarderImpl {
+    stop_fd: Scoped<c_int>,
+    transferred: Arc<AtomicU64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Forwarder for ForwarderImpl {
+    fn transferred(&self) -> u64 {
+        self.transferred.load(Relaxed)
+    }
+
+    fn stop(&mut self) {
+        let value: u64 = 1;
+
+        unsafe {
+            write(*self.stop_fd, (&value as *const u64).cast::<c_void>(), size_of::<u64>());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != thread::current().id() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+impl Drop for ForwarderImpl {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+pub fn start_forward(
+    source: FileDescriptor,
+    target: FileDescriptor,
+    pipe_capacity: usize,
+    listener: impl ForwardListener + Send + 'static,
+) -> Result<Box<dyn Forwarder>, Box<dyn Error>> {
+    let stop_fd = Scoped::new_fd(unsafe { syscall(|| eventfd(0, EFD_NONBLOCK | EFD_CLOEXEC))? });
+    let transferred = Arc::new(AtomicU64::new(0));
+
+    let mut forwarding = Forwarding {
+        source: source as RawFd,
+        target: target as RawFd,
+        pipe_capacity: clamp_pipe_capacity(pipe_capacity),
+        stop_fd: *stop_fd,
+        transferred: transferred.clone(),
+    };
+
+    let thread = thread::Builder::new().name("compat-forward".to_owned()).spawn(move || {
+        let result = forwarding.run();
+
+        if !forwarding.is_stopped() {
+            match result {
+                Ok(_) => listener.on_closed(forwarding.transferred.load(Relaxed), None),
+                Err(err) => listener.on_closed(forwarding.transferred.load(Relaxed), Some(&err.to_string())),
+            }
+        }
+    })?;
+
+    Ok(Box::new(ForwarderImpl {
+        stop_fd,
+        transferred,
+        thread: Some(thread),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{File, OpenOptions},
+        io::{Read, Write},
+        os::{
+            fd::{AsRawFd, FromRawFd, RawFd},
+            unix::fs::{MetadataExt, PermissionsExt},
+        },
+        path::Path,
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use crate::{
+        common::file::{FileDescriptor, ForwardListener, SocketFamily, SocketInfo, SocketType},
+        linux::file::{
+            check_pipe, clamp_pipe_capacity, create_pipe, create_private_directory, create_sealed_file, create_socket_pair,
+            get_socket_info, lock_file, start_forward, unlock_file, write_file_atomically,
+        },
+    };
+
+    #[test]
+    pub fn test_create_private_directory() -> Result<(), Box<dyn Error>> {
+        let outside = std::env::temp_dir().join(format!("compat-private-outside-{}", std::process::id()));
+        std::fs::create_dir_all(&outside)?;
+        std::fs::write(outside.join("keep"), "keep")?;
+
+        let directory = create_private_directory("compat-test")?;
+        let path = std::path::PathBuf::from(directory.path());
+
+        let metadata = std::fs::symlink_metadata(&path)?;
+        assert!(metadata.is_dir());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+
+        std::fs::create_dir_all(path.join("cache/nested"))?;
+        std::fs::write(path.join("cache/nested/data"), "data")?;
+        std::os::unix::fs::symlink(&outside, path.join("outside"))?;
+
+        drop(directory);
+
+        assert!(!path.exists());
+        assert!(outside.join("keep").exists());
+
+        let directory = create_private_directory("compat-test")?;
+        let path = std::path::PathBuf::from(directory.path());
+
+        std::fs::remove_dir(&path)?;
+        std::os::unix::fs::symlink(&outside, &path)?;
+
+        drop(directory);
+
+        assert!(outside.join("keep").exists());
+
+        std::fs::remove_file(&path)?;
+        std::fs::remove_dir_all(&outside)?;
+
+        assert!(create_private_directory("../escape").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_get_socket_info() -> Result<(), Box<dyn Error>> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        assert_eq!(
+            get_socket_info(listener.as_raw_fd() as FileDescriptor)?,
+            SocketInfo {
+                family: SocketFamily::Inet,
+                socket_type: SocketType::Stream,
+                listening: true,
+            }
+        );
+
+        let socket = std::net::UdpSocket::bind("[::1]:0")?;
+        assert_eq!(
+            get_socket_info(socket.as_raw_fd() as FileDescriptor)?,
+            SocketInfo {
+                family: SocketFamily::Inet6,
+                socket_type: SocketType::Datagram,
+                listening: false,
+            }
+        );
+
+        let (first, second) = create_socket_pair()?;
+        assert_eq!(
+            get_socket_info(first)?,
+            SocketInfo {
+                family: SocketFamily::Unix,
+                socket_type: SocketType::Stream,
+                listening: false,
+            }
+        );
+
+        let (reader, writer) = create_pipe()?;
+        assert!(get_socket_info(reader).is_err());
+
+        check_pipe(reader, false)?;
+        check_pipe(writer, true)?;
+        assert!(check_pipe(reader, true).is_err());
+        assert!(check_pipe(writer, false).is_err());
+        assert!(check_pipe(first, false).is_err());
+
+        for fd in [first, second, reader, writer] {
+            unsafe { libc::close(fd as RawFd) };
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_create_sealed_file() -> Result<(), Box<dyn Error>> {
+        let fd = create_sealed_file("sealed-test", b"mixed-port: 7890")?;
+        let mut file = unsafe { File::from_raw_fd(fd as RawFd) };
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        assert_eq!(content, "mixed-port: 7890");
+
+        assert!(file.write_all(b"secret").is_err());
+        assert!(file.set_len(0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_lock_file() -> Result<(), Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!("compat-lock-{}", std::process::id()));
+
+        let first = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        let second = OpenOptions::new().read(true).write(true).open(&path)?;
+        let first_fd = first.as_raw_fd() as FileDescriptor;
+        let second_fd = second.as_raw_fd() as FileDescriptor;
+
+        assert!(lock_file(first_fd, true, Some(Duration::ZERO))?);
+        assert!(!lock_file(second_fd, false, Some(Duration::ZERO))?);
+        assert!(!lock_file(second_fd, false, Some(Duration::from_millis(100)))?);
+
+        unlock_file(first_fd)?;
+
+        assert!(lock_file(second_fd, false, None)?);
+        assert!(lock_file(first_fd, false, Some(Duration::ZERO))?);
+        assert!(!lock_file(first_fd, true, Some(Duration::ZERO))?);
+
+        drop(second);
+
+        assert!(lock_file(first_fd, true, Some(Duration::ZERO))?);
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_write_file_atomically() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("compat-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join("config.yaml");
+        let backup = dir.join("config.yaml.bak");
+
+        write_file_atomically(&path, b"mixed-port: 7890", None)?;
+        assert_eq!(std::fs::read_to_string(&path)?, "mixed-port: 7890");
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640))?;
+
+        write_file_atomically(&path, b"mixed-port: 7891", Some(&backup))?;
+        assert_eq!(std::fs::read_to_string(&path)?, "mixed-port: 7891");
+        assert_eq!(std::fs::read_to_string(&backup)?, "mixed-port: 7890");
+        assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(std::fs::metadata(&backup)?.permissions().mode() & 0o7777, 0o640);
+
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 2);
+
+        // Backup on another filesystem falls back to copying the original
+        let shm = Path::new("/dev/shm");
+        if shm.is_dir() && std::fs::metadata(shm)?.dev() != std::fs::metadata(&dir)?.dev() {
+            let backup = shm.join(format!("compat-atomic-{}.bak", std::process::id()));
+
+            write_file_atomically(&path, b"mixed-port: 7892", Some(&backup))?;
+            assert_eq!(std::fs::read_to_string(&path)?, "mixed-port: 7892");
+            assert_eq!(std::fs::read_to_string(&backup)?, "mixed-port: 7891");
+            assert_eq!(std::fs::metadata(&backup)?.permissions().mode() & 0o7777, 0o640);
+            assert_eq!(std::fs::read_dir(&dir)?.count(), 2);
+
+            std::fs::remove_file(&backup)?;
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    struct ForwardListenerImpl(mpsc::Sender<(u64, Option<String>)>);
+
+    impl ForwardListener for ForwardListenerImpl {
+        fn on_closed(&self, transferred: u64, error: Option<&str>) {
+            self.0.send((transferred, error.map(|s| s.to_owned()))).ok();
+        }
+    }
+
+    #[test]
+    pub fn test_forward_socket() -> Result<(), Box<dyn Error>> {
+        let (source_writer, source_reader) = create_socket_pair()?;
+        let (target_writer, target_reader) = create_socket_pair()?;
+
+        let mut source_writer = unsafe { File::from_raw_fd(source_writer as RawFd) };
+        let source_reader = unsafe { File::from_raw_fd(source_reader as RawFd) };
+        let target_writer = unsafe { File::from_raw_fd(target_writer as RawFd) };
+        let mut target_reader = unsafe { File::from_raw_fd(target_reader as RawFd) };
+
+        let (tx, rx) = mpsc::channel();
+        let forwarder = start_forward(
+            source_reader.as_raw_fd() as FileDescriptor,
+            target_writer.as_raw_fd() as FileDescriptor,
+            0,
+            ForwardListenerImpl(tx),
+        )?;
+
+        source_writer.write_all(b"mixed-port: 7890")?;
+
+        let mut buffer = [0u8; 16];
+        target_reader.read_exact(&mut buffer)?;
+        assert_eq!(&buffer, b"mixed-port: 7890");
+
+        drop(source_writer);
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5))?, (16, None));
+        assert_eq!(forwarder.transferred(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_forward_file() -> Result<(), Box<dyn Error>> {
+        let content = vec![0x5au8; 3 << 20];
+        let source = unsafe { File::from_raw_fd(create_sealed_file("forward-source", &content)? as RawFd) };
+
+        let path = std::env::temp_dir().join(format!("compat-forward-{}", std::process::id()));
+        let target = File::create(&path)?;
+
+        let (tx, rx) = mpsc::channel();
+        let _forwarder = start_forward(
+            source.as_raw_fd() as FileDescriptor,
+            target.as_raw_fd() as FileDescriptor,
+            0,
+            ForwardListenerImpl(tx),
+        )?;
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5))?, (content.len() as u64, None));
+        assert_eq!(std::fs::read(&path)?, content);
+
+        std::fs::remove_file(&path)?;
+
+        let source = unsafe { File::from_raw_fd(create_sealed_file("forward-source", &content)? as RawFd) };
+        let (reader, writer) = create_pipe()?;
+        let mut reader = unsafe { File::from_raw_fd(reader as RawFd) };
+        let writer = unsafe { File::from_raw_fd(writer as RawFd) };
+
+        let (tx, rx) = mpsc::channel();
+        let _forwarder = start_forward(
+            source.as_raw_fd() as FileDescriptor,
+            writer.as_raw_fd() as FileDescriptor,
+            1 << 20,
+            ForwardListenerImpl(tx),
+        )?;
+
+        let mut received = vec![0u8; content.len()];
+        reader.read_exact(&mut received)?;
+        assert_eq!(received, content);
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5))?, (content.len() as u64, None));
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_clamp_pipe_capacity() {
+        assert_eq!(clamp_pipe_capacity(0), 1 << 16);
+        assert_eq!(clamp_pipe_capacity(4096), 4096);
+        assert!(clamp_pipe_capacity(usize::MAX) <= 1 << 20);
+    }
+}
@@ -1,11 +1,15 @@
 pub mod file;
+pub mod netlink;
 pub mod network;
 pub mod notification;
 pub mod notifier;
+pub mod port;
 pub mod process;
 pub mod security;
 pub mod shell;
 pub mod theme;
+pub mod tun;
+pub mod watcher;
 pub mod window;
 
 mod dbus;
@@ -0,0 +1,152 @@
+use std::error::Error;
+
+use libc::{
+    AF_UNSPEC, IFA_ADDRESS, IFA_LOCAL, IFLA_ADDRESS, IFLA_IFNAME, IFLA_INFO_KIND, IFLA_LINKINFO, IFLA_MTU, IFLA_OPERSTATE,
+    RTM_GETADDR, RTM_GETLINK, RTM_NEWADDR, RTM_NEWLINK,
+};
+
+use crate::{
+    common::network::{InterfaceAddress, NetworkInterface, OperState},
+    linux::netlink::{
+        attr_to_ip, attr_to_string, attr_to_u32, parse_attrs, parse_header, IfAddrMsg, IfInfoMsg, Message, Netlink,
+    },
+};
+
+fn parse_oper_state(value: u8) -> OperState {
+    match value {
+        1 => OperState::NotPresent,
+        2 => OperState::Down,
+        3 => OperState::LowerLayerDown,
+        4 => OperState::Testing,
+        5 => OperState::Dormant,
+        6 => OperState::Up,
+        _ => OperState::Unknown,
+    }
+}
+
+pub fn parse_link(payload: &[u8]) -> Option<NetworkInterface> {
+    let (header, attrs) = parse_header::<IfInfoMsg>(payload)?;
+
+    let mut interface = NetworkInterface {
+        index: header.index as u32,
+        flags: header.flags,
+        link_type: header.kind,
+        ..Default::default()
+    };
+
+    for (kind, value) in parse_attrs(attrs) {
+        match kind {
+            IFLA_IFNAME => interface.name = attr_to_string(value),
+            IFLA_MTU => interface.mtu = attr_to_u32(value).unwrap_or_default(),
+            IFLA_ADDRESS if !value.is_empty() => interface.hardware_address = Some(value.to_vec()),
+            IFLA_OPERSTATE if !value.is_empty() => interface.oper_state = parse_oper_state(value[0]),
+            IFLA_LINKINFO => {
+                interface.kind = parse_attrs(value)
+                    .find(|(kind, _)| *kind == IFLA_INFO_KIND)
+                    .map(|(_, value)| attr_to_string(value));
+            }
+            _ => {}
+        }
+    }
+
+    Some(interface)
+}
+
+pub fn parse_address(payload: &[u8]) -> Option<(u32, InterfaceAddress)> {
+    let (header, attrs) = parse_header::<IfAddrMsg>(payload)?;
+
+    let mut local = None;
+    let mut address = None;
+
+    for (kind, value) in parse_attrs(attrs) {
+        match kind {
+            IFA_LOCAL => local = attr_to_ip(header.family, value),
+            IFA_ADDRESS => address = attr_to_ip(header.family, value),
+            _ => {}
+        }
+    }
+
+    // IFA_ADDRESS is the peer address on point-to-point links, prefer IFA_LOCAL when present
+    Some((
+        header.index,
+        InterfaceAddress {
+            address: local.or(address)?,
+            prefix_len: header.prefix_len,
+        },
+    ))
+}
+
+pub fn list_interfaces() -> Result<Vec<NetworkInterface>, Box<dyn Error>> {
+    let mut netlink = Netlink::open()?;
+
+    let links = netlink.dump(Message::new(
+        RTM_GETLINK,
+        0,
+        &IfInfoMsg {
+            family: AF_UNSPEC as u8,
+            ..Default::default()
+        },
+    ))?;
+    let addresses = netlink.dump(Message::new(
+        RTM_GETADDR,
+        0,
+        &IfAddrMsg {
+            family: AF_UNSPEC as u8,
+            ..Default::default()
+        },
+    ))?;
+
+    let mut interfaces = links
+        .iter()
+        .filter(|response| response.kind == RTM_NEWLINK)
+        .filter_map(|response| parse_link(&response.payload))
+        .collect::<Vec<_>>();
+
+    for (index, address) in addresses
+        .iter()
+        .filter(|response| response.kind == RTM_NEWADDR)
+        .filter_map(|response| parse_address(&response.payload))
+    {
+        if let Some(interface) = interfaces.iter_mut().find(|i| i.index == index) {
+            interface.addresses.push(address);
+        }
+    }
+
+    Ok(interfaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        net::{IpAddr, Ipv4Addr},
+    };
+
+    use libc::{ARPHRD_LOOPBACK, IFF_LOOPBACK};
+
+    use crate::{common::network::InterfaceAddress, linux::netlink::link::list_interfaces};
+
+    #[test]
+    pub fn test_list_interfaces() -> Result<(), Box<dyn Error>> {
+        let interfaces = list_interfaces()?;
+
+        let lo = interfaces
+            .iter()
+            .find(|i| i.flags & IFF_LOOPBACK as u32 != 0)
+            .ok_or("loopback not found")?;
+
+        assert_eq!(lo.link_type, ARPHRD_LOOPBACK);
+        assert!(lo.mtu > 0);
+        assert!(lo.addresses.contains(&InterfaceAddress {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            prefix_len: 8,
+        }));
+
+        for interface in &interfaces {
+            assert!(!interface.name.is_empty());
+            assert!(interface.index > 0);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    error::Error,
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ptr::read_unaligned,
+};
+
+use errno::Errno;
+use libc::{
+    bind, c_int, nlmsghdr, recv, send, sockaddr, sockaddr_nl, socket, socklen_t, AF_INET, AF_INET6, AF_NETLINK, EINTR,
+    NETLINK_ROUTE, NLA_TYPE_MASK, NLMSG_DONE, NLMSG_ERROR, NLMSG_MIN_TYPE, NLM_F_ACK, NLM_F_DUMP, NLM_F_REQUEST, SOCK_CLOEXEC,
+    SOCK_RAW,
+};
+
+use crate::{linux::errno::syscall, utils::scoped::Scoped};
+
+pub mod link;
+pub mod monitor;
+pub mod route;
+pub mod rule;
+
+// Protocol id tagging routes and rules installed by us, so they can be found again after a crash
+pub const RTPROT_COMPAT: u8 = 0x7c;
+
+// Tests touching the routing tables must not run concurrently, monitor would observe changes of others
+#[cfg(test)]
+pub static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct IfInfoMsg {
+    pub family: u8,
+    pub pad: u8,
+    pub kind: u16,
+    pub index: i32,
+    pub flags: u32,
+    pub change: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct IfAddrMsg {
+    pub family: u8,
+    pub prefix_len: u8,
+    pub flags: u8,
+    pub scope: u8,
+    pub index: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RtMsg {
+    pub family: u8,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u8,
+    pub protocol: u8,
+    pub scope: u8,
+    pub kind: u8,
+    pub flags: u32,
+}
+
+pub const fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts((value as *const T).cast(), size_of::<T>()) }
+}
+
+pub struct Message {
+    buffer: Vec<u8>,
+}
+
+impl Message {
+    pub fn new<H: Copy>(kind: u16, flags: c_int, header: &H) -> Self {
+        let mut buffer = Vec::with_capacity(256);
+
+        buffer.extend_from_slice(as_bytes(&nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: kind,
+            nlmsg_flags: (flags | NLM_F_REQUEST | NLM_F_ACK) as u16,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        }));
+        buffer.extend_from_slice(as_bytes(header));
+        buffer.resize(align(buffer.len()), 0);
+
+        Self { buffer }
+    }
+
+    pub fn attr(mut self, kind: u16, data: &[u8]) -> Self {
+        let length = size_of::<u16>() * 2 + data.len();
+
+        self.buffer.extend_from_slice(&(length as u16).to_ne_bytes());
+        self.buffer.extend_from_slice(&kind.to_ne_bytes());
+        self.buffer.extend_from_slice(data);
+        self.buffer.resize(align(self.buffer.len()), 0);
+
+        self
+    }
+}
+
+pub struct Response {
+    pub kind: u16,
+    pub payload: Vec<u8>,
+}
+
+pub struct Netlink {
+    fd: Scoped<c_int>,
+    sequence: u32,
+}
+
+impl Netlink {
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        Self::subscribe(0)
+    }
+
+    pub fn subscribe(groups: u32) -> Result<Self, Box<dyn Error>> {
+        let fd = Scoped::new_fd(unsafe { syscall(|| socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_ROUTE))? });
+
+        let mut address: sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = AF_NETLINK as _;
+        address.nl_groups = groups;
+
+        unsafe {
+            syscall(|| {
+                bind(
+                    *fd,
+                    (&address as *const sockaddr_nl).cast::<sockaddr>(),
+                    size_of::<sockaddr_nl>() as socklen_t,
+                )
+            })?;
+        }
+
+        Ok(Self { fd, sequence: 0 })
+    }
+
+    fn transact(&mut self, mut message: Message, mut on_message: impl FnMut(u16, &[u8])) -> Result<(), Box<dyn Error>> {
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let length = message.buffer.len() as u32;
+        message.buffer[0..4].copy_from_slice(&length.to_ne_bytes());
+        message.buffer[8..12].copy_from_slice(&self.sequence.to_ne_bytes());
+
+        unsafe { syscall(|| send(*self.fd, message.buffer.as_ptr().cast(), message.buffer.len(), 0))? };
+
+        let mut buffer = vec![0u8; 32 * 1024];
+
+        loop {
+            let length = match unsafe { syscall(|| recv(*self.fd, buffer.as_mut_ptr().cast(), buffer.len(), 0)) } {
+                Ok(length) => length as usize,
+                Err(err) if err.0 == EINTR => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            let mut offset = 0;
+            while offset + size_of::<nlmsghdr>() <= length {
+                let header: nlmsghdr = unsafe { read_unaligned(buffer[offset..].as_ptr().cast()) };
+                if (header.nlmsg_len as usize) < size_of::<nlmsghdr>() || offset + header.nlmsg_len as usize > length {
+                    return Err("truncated netlink message".into());
+                }
+
+                let payload = &buffer[offset + size_of::<nlmsghdr>()..offset + header.nlmsg_len as usize];
+
+                offset += align(header.nlmsg_len as usize);
+
+                if header.nlmsg_seq != self.sequence {
+                    continue;
+                }
+
+                match header.nlmsg_type as c_int {
+                    NLMSG_ERROR | NLMSG_DONE if payload.len() >= size_of::<c_int>() => {
+                        let error: c_int = unsafe { read_unaligned(payload.as_ptr().cast()) };
+
+                        return if error == 0 { Ok(()) } else { Err(Errno(-error).into()) };
+                    }
+                    NLMSG_DONE => return Ok(()),
+                    kind if kind < NLMSG_MIN_TYPE => {}
+                    _ => on_message(header.nlmsg_type, payload),
+                }
+            }
+        }
+    }
+
+    pub fn execute(&mut self, message: Message) -> Result<(), Box<dyn Error>> {
+        self.transact(message, |_, _| {})
+    }
+
+    pub fn dump(&mut self, mut message: Message) -> Result<Vec<Response>, Box<dyn Error>> {
+        let flags = u16::from_ne_bytes([message.buffer[6], message.buffer[7]]);
+        message.buffer[6..8].copy_from_slice(&(flags | NLM_F_DUMP as u16).to_ne_bytes());
+
+        let mut messages = Vec::new();
+
+        self.transact(message, |kind, payload| {
+            messages.push(Response {
+                kind,
+                payload: payload.to_vec(),
+            })
+        })?;
+
+        Ok(messages)
+    }
+}
+
+pub fn parse_header<H: Copy>(payload: &[u8]) -> Option<(H, &[u8])> {
+    if payload.len() < size_of::<H>() {
+        return None;
+    }
+
+    let header: H = unsafe { read_unaligned(payload.as_ptr().cast()) };
+
+    Some((header, payload.get(align(size_of::<H>())..).unwrap_or_default()))
+}
+
+pub fn parse_attrs(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < size_of::<u16>() * 2 {
+            return None;
+        }
+
+        let length = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let kind = u16::from_ne_bytes([data[2], data[3]]) & NLA_TYPE_MASK as u16;
+        if length < size_of::<u16>() * 2 || length > data.len() {
+            return None;
+        }
+
+        let value = &data[size_of::<u16>() * 2..length];
+
+        data = data.get(align(length)..).unwrap_or_default();
+
+        Some((kind, value))
+    })
+}
+
+pub fn attr_to_ip(family: u8, value: &[u8]) -> Option<IpAddr> {
+    match family as c_int {
+        AF_INET => Some(Ipv4Addr::from(<[u8; 4]>::try_from(value).ok()?).into()),
+        AF_INET6 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(value).ok()?).into()),
+        _ => None,
+    }
+}
+
+pub fn attr_to_u32(value: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(value.try_into().ok()?))
+}
+
+pub fn attr_to_string(value: &[u8]) -> String {
+    let value = value.split(|c| *c == 0).next().unwrap_or_default();
+
+    String::from_utf8_lossy(value).into_owned()
+}
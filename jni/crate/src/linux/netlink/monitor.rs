@@ -0,0 +1,246 @@
+use std::{
+    error::Error,
+    mem::size_of,
+    ptr::read_unaligned,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use libc::{
+    c_int, c_void, eventfd, nlmsghdr, poll, pollfd, recv, write, EAGAIN, EFD_CLOEXEC, EFD_NONBLOCK, EINTR, ENOBUFS, MSG_DONTWAIT,
+    POLLIN, RTMGRP_IPV4_IFADDR, RTMGRP_IPV4_ROUTE, RTMGRP_IPV6_IFADDR, RTMGRP_IPV6_ROUTE, RTMGRP_LINK, RTM_DELADDR, RTM_DELLINK,
+    RTM_DELROUTE, RTM_NEWADDR, RTM_NEWLINK, RTM_NEWROUTE,
+};
+
+use crate::{
+    common::network::{ChangeListener, Holder, CHANGE_ADDRESS, CHANGE_LINK, CHANGE_ROUTE},
+    linux::{
+        errno::syscall,
+        netlink::{align, Netlink},
+    },
+    utils::scoped::Scoped,
+};
+
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(500);
+const DEBOUNCE_LIMIT: Duration = Duration::from_secs(3);
+
+const CHANGE_ALL: u32 = CHANGE_LINK | CHANGE_ADDRESS | CHANGE_ROUTE;
+
+fn message_changes(kind: u16) -> u32 {
+    match kind {
+        RTM_NEWLINK | RTM_DELLINK => CHANGE_LINK,
+        RTM_NEWADDR | RTM_DELADDR => CHANGE_ADDRESS,
+        RTM_NEWROUTE | RTM_DELROUTE => CHANGE_ROUTE,
+        _ => 0,
+    }
+}
+
+struct Monitoring {
+    netlink: Netlink,
+    stop_fd: c_int,
+}
+
+impl Monitoring {
+    fn drain(&mut self) -> Result<u32, Box<dyn Error>> {
+        let mut buffer = vec![0u8; 32 * 1024];
+        let mut changes = 0;
+
+        loop {
+            let length =
+                match unsafe { syscall(|| recv(*self.netlink.fd, buffer.as_mut_ptr().cast(), buffer.len(), MSG_DONTWAIT)) } {
+                    Ok(length) => length as usize,
+                    Err(err) if err.0 == EINTR => continue,
+                    Err(err) if err.0 == EAGAIN => return Ok(changes),
+                    // Socket buffer overrun, some notifications are lost
+                    Err(err) if err.0 == ENOBUFS => {
+                        changes |= CHANGE_ALL;
+
+                        continue;
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+
+            if length == 0 {
+                return Err("netlink socket closed".into());
+            }
+
+            let mut offset = 0;
+            while offset + size_of::<nlmsghdr>() <= length {
+                let header: nlmsghdr = unsafe { read_unaligned(buffer[offset..].as_ptr().cast()) };
+                if (header.nlmsg_len as usize) < size_of::<nlmsghdr>() {
+                    break;
+                }
+
+                changes |= message_changes(header.nlmsg_type);
+
+                offset += align(header.nlmsg_len as usize);
+            }
+        }
+    }
+
+    // Monitoring stops once the socket fails, report everything as changed so the state is refreshed one last time
+    fn run(mut self, listener: impl ChangeListener) {
+        let mut pending = 0;
+        let mut first: Option<Instant> = None;
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let timeout = match deadline {
+                // Round up, a truncated timeout wakes up early and spins until the deadline
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_micros().div_ceil(1000) as c_int,
+                None => -1,
+            };
+
+            let mut fds = [
+                pollfd {
+                    fd: *self.netlink.fd,
+                    events: POLLIN,
+                    revents: 0,
+                },
+                pollfd {
+                    fd: self.stop_fd,
+                    events: POLLIN,
+                    revents: 0,
+                },
+            ];
+
+            if let Err(err) = unsafe { syscall(|| poll(fds.as_mut_ptr(), fds.len() as _, timeout)) } {
+                if err.0 == EINTR {
+                    continue;
+                }
+
+                listener.on_network_changed(CHANGE_ALL);
+
+                return;
+            }
+
+            if fds[1].revents != 0 {
+                return;
+            }
+
+            if fds[0].revents != 0 {
+                let changes = match self.drain() {
+                    Ok(changes) => changes,
+                    Err(_) => {
+                        listener.on_network_changed(CHANGE_ALL);
+
+                        return;
+                    }
+                };
+
+                if changes != 0 {
+                    let now = Instant::now();
+                    let first = *first.get_or_insert(now);
+
+                    pending |= changes;
+                    deadline = Some((now + DEBOUNCE_WINDOW).min(first + DEBOUNCE_LIMIT));
+                }
+            }
+
+            if deadline.map(|d| d <= Instant::now()).unwrap_or(false) {
+                listener.on_network_changed(pending);
+
+                pending = 0;
+                first = None;
+                deadline = None;
+            }
+        }
+    }
+}
+
+struct HolderImpl {
+    stop_fd: Scoped<c_int>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Holder for HolderImpl {}
+
+impl Drop for HolderImpl {
+    fn drop(&mut self) {
+        let value: u64 = 1;
+
+        unsafe {
+            write(*self.stop_fd, (&value as *const u64).cast::<c_void>(), size_of::<u64>());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != std::thread::current().id() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+pub fn add_network_changed_listener(listener: impl ChangeListener + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let netlink = Netlink::subscribe(
+        (RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR | RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE) as u32,
+    )?;
+
+    let stop_fd = Scoped::new_fd(unsafe { syscall(|| eventfd(0, EFD_NONBLOCK | EFD_CLOEXEC))? });
+
+    let monitoring = Monitoring {
+        netlink,
+        stop_fd: *stop_fd,
+    };
+
+    let thread = std::thread::Builder::new()
+        .name("compat-network-monitor".to_owned())
+        .spawn(move || monitoring.run(listener))?;
+
+    Ok(Box::new(HolderImpl {
+        stop_fd,
+        thread: Some(thread),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, sync::mpsc, time::Duration};
+
+    use crate::{
+        common::network::{ChangeListener, InterfaceAddress, Route, CHANGE_ROUTE},
+        linux::netlink::{
+            monitor::add_network_changed_listener,
+            route::{add_route, delete_route},
+            TEST_LOCK,
+        },
+    };
+
+    struct ListenerImpl(mpsc::Sender<u32>);
+
+    impl ChangeListener for ListenerImpl {
+        fn on_network_changed(&self, changes: u32) {
+            self.0.send(changes).ok();
+        }
+    }
+
+    #[test]
+    pub fn test_network_monitor() -> Result<(), Box<dyn Error>> {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let (tx, rx) = mpsc::channel();
+        let holder = add_network_changed_listener(ListenerImpl(tx))?;
+
+        let route = Route {
+            destination: "198.19.0.0/16".parse::<InterfaceAddress>()?,
+            gateway: None,
+            interface_index: 0,
+            interface_name: "lo".to_owned(),
+            metric: 0,
+            table: 31338,
+        };
+
+        add_route(&route)?;
+        delete_route(&route)?;
+
+        let changes = rx.recv_timeout(Duration::from_secs(5))?;
+        assert_ne!(changes & CHANGE_ROUTE, 0);
+
+        // Burst of notifications should be delivered once
+        assert!(rx.recv_timeout(Duration::from_secs(1)).is_err());
+
+        drop(holder);
+
+        Ok(())
+    }
+}
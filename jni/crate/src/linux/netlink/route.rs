@@ -0,0 +1,277 @@
+use std::{
+    error::Error,
+    ffi::{CStr, CString},
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use libc::{
+    c_char, c_int, if_indextoname, if_nametoindex, AF_INET, AF_INET6, AF_UNSPEC, IF_NAMESIZE, NLM_F_CREATE, NLM_F_EXCL, RTA_DST,
+    RTA_GATEWAY, RTA_MULTIPATH, RTA_OIF, RTA_PRIORITY, RTA_TABLE, RTM_DELROUTE, RTM_GETROUTE, RTM_NEWROUTE, RTN_UNICAST,
+    RT_SCOPE_LINK, RT_SCOPE_NOWHERE, RT_SCOPE_UNIVERSE, RT_TABLE_MAIN, RT_TABLE_UNSPEC,
+};
+
+use crate::{
+    common::network::{InterfaceAddress, Route},
+    linux::netlink::{align, attr_to_ip, attr_to_u32, parse_attrs, parse_header, Message, Netlink, RtMsg, RTPROT_COMPAT},
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct RtNextHop {
+    length: u16,
+    flags: u8,
+    hops: u8,
+    index: i32,
+}
+
+fn interface_name(index: u32) -> String {
+    let mut name = [0 as c_char; IF_NAMESIZE];
+
+    if unsafe { if_indextoname(index, name.as_mut_ptr()) }.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+fn parse_next_hops(family: u8, mut data: &[u8]) -> Vec<(u32, Option<IpAddr>)> {
+    let mut hops = Vec::new();
+
+    while let Some((hop, attrs)) = parse_header::<RtNextHop>(data) {
+        let length = hop.length as usize;
+        if length < size_of::<RtNextHop>() || length > data.len() {
+            break;
+        }
+
+        let gateway = parse_attrs(&attrs[..length - size_of::<RtNextHop>()])
+            .find(|(kind, _)| *kind == RTA_GATEWAY)
+            .and_then(|(_, value)| attr_to_ip(family, value));
+
+        hops.push((hop.index as u32, gateway));
+
+        data = data.get(align(length)..).unwrap_or_default();
+    }
+
+    hops
+}
+
+pub fn parse_route(payload: &[u8]) -> Option<(RtMsg, Vec<Route>)> {
+    let (header, attrs) = parse_header::<RtMsg>(payload)?;
+
+    let unspecified = match header.family as c_int {
+        AF_INET => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        _ => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let mut destination = unspecified;
+    let mut gateway = None;
+    let mut interface_index = 0;
+    let mut metric = 0;
+    let mut table = header.table as u32;
+    let mut hops = Vec::new();
+
+    for (kind, value) in parse_attrs(attrs) {
+        match kind {
+            RTA_DST => destination = attr_to_ip(header.family, value).unwrap_or(unspecified),
+            RTA_GATEWAY => gateway = attr_to_ip(header.family, value),
+            RTA_OIF => interface_index = attr_to_u32(value).unwrap_or_default(),
+            RTA_PRIORITY => metric = attr_to_u32(value).unwrap_or_default(),
+            RTA_TABLE => table = attr_to_u32(value).unwrap_or(table),
+            RTA_MULTIPATH => hops = parse_next_hops(header.family, value),
+            _ => {}
+        }
+    }
+
+    if hops.is_empty() {
+        hops.push((interface_index, gateway));
+    }
+
+    let routes = hops
+        .into_iter()
+        .map(|(interface_index, gateway)| Route {
+            destination: InterfaceAddress {
+                address: destination,
+                prefix_len: header.dst_len,
+            },
+            gateway,
+            interface_index,
+            interface_name: interface_name(interface_index),
+            metric,
+            table,
+        })
+        .collect();
+
+    Some((header, routes))
+}
+
+fn dump_routes(filter: impl Fn(&RtMsg) -> bool) -> Result<Vec<Route>, Box<dyn Error>> {
+    let responses = Netlink::open()?.dump(Message::new(
+        RTM_GETROUTE,
+        0,
+        &RtMsg {
+            family: AF_UNSPEC as u8,
+            ..Default::default()
+        },
+    ))?;
+
+    Ok(responses
+        .iter()
+        .filter(|response| response.kind == RTM_NEWROUTE)
+        .filter_map(|response| parse_route(&response.payload))
+        .filter(|(header, _)| header.kind == RTN_UNICAST && filter(header))
+        .flat_map(|(_, routes)| routes)
+        .collect())
+}
+
+pub fn list_default_routes() -> Result<Vec<Route>, Box<dyn Error>> {
+    // Routes we installed, such as the default route through tun, must never be taken as physical ones
+    let mut routes = dump_routes(|header| header.dst_len == 0 && header.protocol != RTPROT_COMPAT)?
+        .into_iter()
+        .filter(|route| route.table == RT_TABLE_MAIN as u32 && route.interface_index != 0)
+        .collect::<Vec<_>>();
+
+    routes.sort_by_key(|route| (route.destination.address.is_ipv6(), route.metric));
+
+    Ok(routes)
+}
+
+pub fn list_installed_routes() -> Result<Vec<Route>, Box<dyn Error>> {
+    dump_routes(|header| header.protocol == RTPROT_COMPAT)
+}
+
+fn ip_octets(address: &IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    }
+}
+
+fn route_message(kind: u16, flags: c_int, route: &Route) -> Result<Message, Box<dyn Error>> {
+    let interface_index = if route.interface_index != 0 {
+        route.interface_index
+    } else {
+        match unsafe { if_nametoindex(CString::new(route.interface_name.as_str())?.as_ptr()) } {
+            0 => return Err(format!("interface not found: {}", route.interface_name).into()),
+            index => index,
+        }
+    };
+
+    let destination = &route.destination;
+    let ipv6 = destination.address.is_ipv6();
+    if route.gateway.map(|gateway| gateway.is_ipv6() != ipv6).unwrap_or(false) {
+        return Err("gateway family mismatch".into());
+    }
+
+    let scope = match kind {
+        RTM_DELROUTE => RT_SCOPE_NOWHERE,
+        _ if !ipv6 && route.gateway.is_none() => RT_SCOPE_LINK,
+        _ => RT_SCOPE_UNIVERSE,
+    };
+
+    let header = RtMsg {
+        family: if ipv6 { AF_INET6 } else { AF_INET } as u8,
+        dst_len: destination.prefix_len,
+        table: u8::try_from(route.table).unwrap_or(RT_TABLE_UNSPEC),
+        protocol: RTPROT_COMPAT,
+        scope,
+        kind: if kind == RTM_DELROUTE { 0 } else { RTN_UNICAST },
+        ..Default::default()
+    };
+
+    let mut message = Message::new(kind, flags, &header)
+        .attr(RTA_TABLE, &route.table.to_ne_bytes())
+        .attr(RTA_OIF, &interface_index.to_ne_bytes())
+        .attr(RTA_PRIORITY, &route.metric.to_ne_bytes());
+    if destination.prefix_len > 0 {
+        message = message.attr(RTA_DST, &ip_octets(&destination.address));
+    }
+    if let Some(gateway) = &route.gateway {
+        message = message.attr(RTA_GATEWAY, &ip_octets(gateway));
+    }
+
+    Ok(message)
+}
+
+pub fn add_route(route: &Route) -> Result<(), Box<dyn Error>> {
+    Netlink::open()?.execute(route_message(RTM_NEWROUTE, NLM_F_CREATE | NLM_F_EXCL, route)?)
+}
+
+pub fn delete_route(route: &Route) -> Result<(), Box<dyn Error>> {
+    Netlink::open()?.execute(route_message(RTM_DELROUTE, 0, route)?)
+}
+
+pub fn flush_installed_routes() -> Result<(), Box<dyn Error>> {
+    let mut netlink = Netlink::open()?;
+
+    for route in list_installed_routes()? {
+        netlink.execute(route_message(RTM_DELROUTE, 0, &route)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        net::{IpAddr, Ipv4Addr},
+        process::Command,
+    };
+
+    use crate::{
+        common::network::{InterfaceAddress, Route},
+        linux::netlink::{
+            route::{add_route, delete_route, list_default_routes},
+            TEST_LOCK,
+        },
+    };
+
+    #[test]
+    pub fn test_list_default_routes() -> Result<(), Box<dyn Error>> {
+        let routes = list_default_routes()?;
+
+        let output = Command::new("ip").args(["-4", "route", "show", "default"]).output()?;
+        let expected = String::from_utf8(output.stdout)?;
+
+        for line in expected.lines() {
+            let route = routes
+                .iter()
+                .find(|r| r.destination.address.is_ipv4() && line.contains(&format!("dev {} ", r.interface_name)))
+                .ok_or(format!("missing route: {line}"))?;
+
+            if let Some(gateway) = route.gateway {
+                assert!(line.contains(&format!("via {gateway} ")));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_list_default_routes_excludes_installed() -> Result<(), Box<dyn Error>> {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let route = Route {
+            destination: InterfaceAddress {
+                address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                prefix_len: 0,
+            },
+            gateway: None,
+            interface_index: 0,
+            interface_name: "lo".to_owned(),
+            metric: 7,
+            table: 31338,
+        };
+
+        add_route(&route)?;
+
+        let routes = list_default_routes();
+
+        delete_route(&route)?;
+
+        assert!(routes?.iter().all(|r| r.table != route.table && r.interface_name != "lo"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::error::Error;
+
+use libc::{
+    c_int, AF_INET, AF_INET6, AF_UNSPEC, NLM_F_CREATE, NLM_F_EXCL, RTM_DELRULE, RTM_GETRULE, RTM_NEWRULE, RT_TABLE_UNSPEC,
+};
+
+use crate::{
+    common::network::Rule,
+    linux::netlink::{attr_to_u32, parse_attrs, parse_header, Message, Netlink, RTPROT_COMPAT},
+};
+
+const FRA_PRIORITY: u16 = 6;
+const FRA_FWMARK: u16 = 10;
+const FRA_TABLE: u16 = 15;
+const FRA_FWMASK: u16 = 16;
+const FRA_PROTOCOL: u16 = 21;
+
+const FR_ACT_TO_TBL: u8 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FibRuleHdr {
+    pub family: u8,
+    pub dst_len: u8,
+    pub src_len: u8,
+    pub tos: u8,
+    pub table: u8,
+    pub res1: u8,
+    pub res2: u8,
+    pub action: u8,
+    pub flags: u32,
+}
+
+pub fn parse_rule(payload: &[u8]) -> Option<(u8, Rule)> {
+    let (header, attrs) = parse_header::<FibRuleHdr>(payload)?;
+
+    let mut protocol = 0;
+    let mut rule = Rule {
+        ipv6: header.family as c_int == AF_INET6,
+        fwmark: 0,
+        priority: 0,
+        table: header.table as u32,
+    };
+
+    for (kind, value) in parse_attrs(attrs) {
+        match kind {
+            FRA_PRIORITY => rule.priority = attr_to_u32(value).unwrap_or_default(),
+            FRA_FWMARK => rule.fwmark = attr_to_u32(value).unwrap_or_default(),
+            FRA_TABLE => rule.table = attr_to_u32(value).unwrap_or(rule.table),
+            FRA_PROTOCOL if !value.is_empty() => protocol = value[0],
+            _ => {}
+        }
+    }
+
+    Some((protocol, rule))
+}
+
+fn rule_message(kind: u16, flags: c_int, rule: &Rule) -> Message {
+    let header = FibRuleHdr {
+        family: if rule.ipv6 { AF_INET6 } else { AF_INET } as u8,
+        table: u8::try_from(rule.table).unwrap_or(RT_TABLE_UNSPEC),
+        action: FR_ACT_TO_TBL,
+        ..Default::default()
+    };
+
+    let mut message = Message::new(kind, flags, &header)
+        .attr(FRA_TABLE, &rule.table.to_ne_bytes())
+        .attr(FRA_PROTOCOL, &[RTPROT_COMPAT]);
+    if rule.priority != 0 {
+        message = message.attr(FRA_PRIORITY, &rule.priority.to_ne_bytes());
+    }
+    if rule.fwmark != 0 {
+        message = message
+            .attr(FRA_FWMARK, &rule.fwmark.to_ne_bytes())
+            .attr(FRA_FWMASK, &u32::MAX.to_ne_bytes());
+    }
+
+    message
+}
+
+pub fn list_installed_rules() -> Result<Vec<Rule>, Box<dyn Error>> {
+    let responses = Netlink::open()?.dump(Message::new(
+        RTM_GETRULE,
+        0,
+        &FibRuleHdr {
+            family: AF_UNSPEC as u8,
+            ..Default::default()
+        },
+    ))?;
+
+    Ok(responses
+        .iter()
+        .filter(|response| response.kind == RTM_NEWRULE)
+        .filter_map(|response| parse_rule(&response.payload))
+        .filter(|(protocol, _)| *protocol == RTPROT_COMPAT)
+        .map(|(_, rule)| rule)
+        .collect())
+}
+
+pub fn add_rule(rule: &Rule) -> Result<(), Box<dyn Error>> {
+    Netlink::open()?.execute(rule_message(RTM_NEWRULE, NLM_F_CREATE | NLM_F_EXCL, rule))
+}
+
+pub fn delete_rule(rule: &Rule) -> Result<(), Box<dyn Error>> {
+    Netlink::open()?.execute(rule_message(RTM_DELRULE, 0, rule))
+}
+
+pub fn flush_installed_rules() -> Result<(), Box<dyn Error>> {
+    let mut netlink = Netlink::open()?;
+
+    for rule in list_installed_rules()? {
+        netlink.execute(rule_message(RTM_DELRULE, 0, &rule))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        net::{IpAddr, Ipv4Addr},
+    };
+
+    use crate::{
+        common::network::{InterfaceAddress, Route, Rule},
+        linux::netlink::{
+            route::{add_route, delete_route, list_installed_routes},
+            rule::{add_rule, delete_rule, list_installed_rules},
+            TEST_LOCK,
+        },
+    };
+
+    #[test]
+    pub fn test_routing_policy() -> Result<(), Box<dyn Error>> {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let route = Route {
+            destination: InterfaceAddress {
+                address: IpAddr::V4(Ipv4Addr::new(198, 18, 0, 0)),
+                prefix_len: 15,
+            },
+            gateway: None,
+            interface_index: 0,
+            interface_name: "lo".to_owned(),
+            metric: 7,
+            table: 31337,
+        };
+        let rule = Rule {
+            ipv6: false,
+            fwmark: 0x7c7c,
+            priority: 31337,
+            table: 31337,
+        };
+
+        add_route(&route)?;
+        add_rule(&rule)?;
+
+        let routes = list_installed_routes()?;
+        assert!(routes
+            .iter()
+            .any(|r| r.destination == route.destination && r.table == route.table && r.metric == route.metric));
+        assert!(list_installed_rules()?.contains(&rule));
+
+        // Only clean up what this test installed, flushing would also remove routes of a running instance
+        delete_rule(&rule)?;
+        delete_route(&route)?;
+
+        assert!(!list_installed_rules()?.contains(&rule));
+        assert!(list_installed_routes()?.iter().all(|r| r.table != route.table));
+
+        Ok(())
+    }
+}
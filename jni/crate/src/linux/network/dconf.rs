@@ -0,0 +1,661 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+const ITEM_SIZE: usize = 24;
+const NO_PARENT: u32 = u32::MAX;
+
+// Serialized gvariant of type `v` in native byte order, the value followed by a nul and its signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant(Vec<u8>);
+
+impl Variant {
+    fn new(mut value: Vec<u8>, signature: &str) -> Self {
+        value.push(0);
+        value.extend_from_slice(signature.as_bytes());
+
+        Variant(value)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        bytes.contains(&0).then_some(Variant(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn split(&self) -> (&[u8], &[u8]) {
+        let index = self.0.iter().rposition(|b| *b == 0).unwrap_or_default();
+
+        (&self.0[..index], &self.0[index + 1..])
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.split() {
+            (value, b"s") => std::str::from_utf8(value.strip_suffix(&[0])?).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self.split() {
+            (value, b"i") => Some(i32::from_ne_bytes(value.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn as_strings(&self) -> Option<Vec<String>> {
+        match self.split() {
+            (value, b"as") => split_array(value)?
+                .into_iter()
+                .map(|item| Some(std::str::from_utf8(item.strip_suffix(&[0])?).ok()?.to_owned()))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    // Schema keys are stored as a tuple of the default value followed by range and choice metadata
+    fn first_member(&self) -> Option<Variant> {
+        let (value, signature) = self.split();
+        let members = signature.strip_prefix(b"(")?.strip_suffix(b")")?;
+        let first = &members[..type_length(members)?];
+
+        let bytes = match fixed_size(first) {
+            Some(size) => value.get(..size)?,
+            None if first.len() == members.len() => value,
+            // End of the first variable sized member is the last framing offset of the tuple
+            None if matches!(first[0], b's' | b'o' | b'g' | b'v' | b'a' | b'm') => {
+                let size = offset_size(value.len());
+                let end = LittleEndian::read_uint(value.get(value.len().checked_sub(size)?..)?, size) as usize;
+
+                value.get(..end)?
+            }
+            None => return None,
+        };
+
+        Some(Variant::new(bytes.to_vec(), std::str::from_utf8(first).ok()?))
+    }
+}
+
+impl From<&str> for Variant {
+    fn from(value: &str) -> Self {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+
+        Variant::new(bytes, "s")
+    }
+}
+
+impl From<i32> for Variant {
+    fn from(value: i32) -> Self {
+        Variant::new(value.to_ne_bytes().to_vec(), "i")
+    }
+}
+
+impl From<bool> for Variant {
+    fn from(value: bool) -> Self {
+        Variant::new(vec![value as u8], "b")
+    }
+}
+
+impl From<&[String]> for Variant {
+    fn from(value: &[String]) -> Self {
+        let mut bytes = Vec::new();
+        let mut ends = Vec::new();
+        for item in value {
+            bytes.extend_from_slice(item.as_bytes());
+            bytes.push(0);
+            ends.push(bytes.len());
+        }
+
+        Variant::new(append_offsets(bytes, &ends), "as")
+    }
+}
+
+// Length of the first complete type of the signature
+fn type_length(signature: &[u8]) -> Option<usize> {
+    match *signature.first()? {
+        b'a' | b'm' => Some(1 + type_length(&signature[1..])?),
+        open @ (b'(' | b'{') => {
+            let close = if open == b'(' { b')' } else { b'}' };
+
+            let mut length = 1;
+            while *signature.get(length)? != close {
+                length += type_length(&signature[length..])?;
+            }
+
+            Some(length + 1)
+        }
+        _ => Some(1),
+    }
+}
+
+fn fixed_size(signature: &[u8]) -> Option<usize> {
+    match signature {
+        b"b" | b"y" => Some(1),
+        b"n" | b"q" => Some(2),
+        b"i" | b"u" | b"h" => Some(4),
+        b"x" | b"t" | b"d" => Some(8),
+        _ => None,
+    }
+}
+
+// Framing offsets are little endian, their size depends on the size of the whole container
+fn offset_size(container_size: usize) -> usize {
+    match container_size {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x10000..=0xffff_ffff => 4,
+        _ => 8,
+    }
+}
+
+fn append_offsets(mut data: Vec<u8>, offsets: &[usize]) -> Vec<u8> {
+    let size = [1, 2, 4, 8]
+        .into_iter()
+        .find(|size| offset_size(data.len() + offsets.len() * size) <= *size)
+        .unwrap_or(8);
+
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes()[..size]);
+    }
+
+    data
+}
+
+fn split_array(data: &[u8]) -> Option<Vec<&[u8]>> {
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let size = offset_size(data.len());
+    let read_offset = |bytes: &[u8]| LittleEndian::read_uint(bytes, size) as usize;
+
+    let table_start = read_offset(data.get(data.len().checked_sub(size)?..)?);
+    let table = data.get(table_start..)?;
+    if table.len() % size != 0 {
+        return None;
+    }
+
+    let mut start = 0;
+    table
+        .chunks(size)
+        .map(|offset| {
+            let end = read_offset(offset);
+            let item = data.get(start..end)?;
+            start = end;
+
+            Some(item)
+        })
+        .collect()
+}
+
+fn pad(data: &mut Vec<u8>, alignment: usize) {
+    data.resize(data.len().next_multiple_of(alignment), 0);
+}
+
+// Serializes the `a{smv}` changeset accepted by the writer service, None resets the key to its default
+pub fn serialize_changeset(changes: &[(String, Option<Variant>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut ends = Vec::new();
+    for (key, value) in changes {
+        pad(&mut data, 8);
+
+        let mut entry = key.as_bytes().to_vec();
+        entry.push(0);
+
+        let key_end = entry.len();
+
+        pad(&mut entry, 8);
+        if let Some(value) = value {
+            entry.extend_from_slice(value.as_bytes());
+            entry.push(0);
+        }
+
+        data.extend(append_offsets(entry, &[key_end]));
+        ends.push(data.len());
+    }
+
+    append_offsets(data, &ends)
+}
+
+fn user_dir(variable: &str, fallback: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match std::env::var_os(variable).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Ok(dir),
+        _ => Ok(home::home_dir().ok_or("home directory not found")?.join(fallback)),
+    }
+}
+
+fn system_data_dirs() -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = std::env::var_os("XDG_DATA_DIRS")
+        .map(|dirs| std::env::split_paths(&dirs).filter(|dir| dir.is_absolute()).collect())
+        .unwrap_or_default();
+
+    if dirs.is_empty() {
+        vec![PathBuf::from("/usr/local/share"), PathBuf::from("/usr/share")]
+    } else {
+        dirs
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Source {
+    User(String),
+    System(PathBuf),
+}
+
+impl Source {
+    fn path(&self) -> Result<PathBuf, Box<dyn Error>> {
+        match self {
+            Source::User(name) => Ok(user_dir("XDG_CONFIG_HOME", ".config")?.join("dconf").join(name)),
+            Source::System(path) => Ok(path.clone()),
+        }
+    }
+}
+
+// One database per line, highest priority first, service databases are not supported
+fn parse_profile(profile: &str) -> Vec<Source> {
+    profile
+        .lines()
+        .filter_map(|line| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if let Some(name) = line.strip_prefix("user-db:") {
+                Some(Source::User(name.to_owned()))
+            } else if let Some(name) = line.strip_prefix("system-db:") {
+                Some(Source::System(Path::new("/etc/dconf/db").join(name)))
+            } else {
+                line.strip_prefix("file-db:").map(|path| Source::System(PathBuf::from(path)))
+            }
+        })
+        .collect()
+}
+
+// Profile lookup of dconf clients, without any profile only the `user` database is used
+fn read_profile() -> Result<Vec<Source>, Box<dyn Error>> {
+    let profile = std::env::var("DCONF_PROFILE").ok();
+
+    let profile_dirs = || {
+        std::iter::once(PathBuf::from("/etc"))
+            .chain(system_data_dirs())
+            .map(|dir| dir.join("dconf").join("profile"))
+    };
+
+    let candidates: Vec<PathBuf> = match &profile {
+        Some(path) if path.starts_with('/') => vec![PathBuf::from(path)],
+        Some(name) => profile_dirs().map(|dir| dir.join(name)).collect(),
+        None => std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("dconf.profile"))
+            .into_iter()
+            .chain(profile_dirs().map(|dir| dir.join("user")))
+            .collect(),
+    };
+
+    for path in candidates {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => return Ok(parse_profile(&content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // A missing named profile leaves dconf with no database at all
+    match profile {
+        Some(_) => Ok(Vec::new()),
+        None => Ok(vec![Source::User("user".to_owned())]),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(LittleEndian::read_u32)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(LittleEndian::read_u16)
+}
+
+// Pointers are absolute offsets into the file
+fn read_range<'a>(data: &'a [u8], pointer: &[u8], offset: usize) -> Option<&'a [u8]> {
+    let start = read_u32(pointer, offset)? as usize;
+    let end = read_u32(pointer, offset + 4)? as usize;
+
+    data.get(start..end)
+}
+
+// Items of a hash table as (full key, item type, value range)
+fn parse_table<'a>(data: &'a [u8], table: &[u8]) -> Option<Vec<(String, u8, &'a [u8])>> {
+    let n_bloom_words = (read_u32(table, 0)? & ((1 << 27) - 1)) as usize;
+    let n_buckets = read_u32(table, 4)? as usize;
+    let items: Vec<&[u8]> = table
+        .get(8 + (n_bloom_words + n_buckets) * 4..)?
+        .chunks_exact(ITEM_SIZE)
+        .collect();
+
+    // Item keys are relative to their parent item, walk up to build the full key
+    let key = |mut index: u32| -> Option<String> {
+        let mut parts = Vec::new();
+        while index != NO_PARENT && parts.len() <= items.len() {
+            let item = items.get(index as usize)?;
+            let start = read_u32(item, 8)? as usize;
+            let size = read_u16(item, 12)? as usize;
+
+            parts.push(data.get(start..start + size)?);
+            index = read_u32(item, 4)?;
+        }
+
+        String::from_utf8(parts.into_iter().rev().flatten().copied().collect()).ok()
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| Some((key(index as u32)?, item[14], read_range(data, item, 16)?)))
+        .collect()
+}
+
+fn parse_root_table(data: &[u8]) -> Option<Vec<(String, u8, &[u8])>> {
+    // Writer service stores values in native byte order, the table layout is always little endian
+    if data.get(..8)? != b"GVariant" {
+        return None;
+    }
+
+    parse_table(data, read_range(data, data, 16)?)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Database {
+    values: BTreeMap<String, Variant>,
+    locks: BTreeSet<String>,
+}
+
+fn parse_database(data: &[u8], prefix: &str) -> Option<Database> {
+    let mut database = Database::default();
+    for (key, kind, value) in parse_root_table(data)? {
+        match kind {
+            b'v' if key.starts_with(prefix) => {
+                database.values.insert(key, Variant::from_bytes(value.to_vec())?);
+            }
+            // System databases keep keys the user must not override in a nested table
+            b'H' if key == ".locks" => {
+                let locks = parse_table(data, value)?;
+
+                database
+                    .locks
+                    .extend(locks.into_iter().map(|(key, _, _)| key).filter(|key| key.starts_with(prefix)));
+            }
+            _ => {}
+        }
+    }
+
+    Some(database)
+}
+
+fn read_database(path: &Path, prefix: &str) -> Result<Database, Box<dyn Error>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Database::default()),
+        Err(err) => return Err(err.into()),
+    };
+
+    parse_database(&data, prefix).ok_or_else(|| format!("invalid dconf database: {}", path.display()).into())
+}
+
+// Default values by schema id, vendor overrides are already applied by the schema compiler
+fn parse_schemas(data: &[u8], prefix: &str) -> Option<BTreeMap<String, BTreeMap<String, Variant>>> {
+    let mut schemas = BTreeMap::new();
+    for (id, kind, table) in parse_root_table(data)? {
+        if kind != b'H' {
+            continue;
+        }
+
+        let keys = parse_table(data, table)?;
+
+        // Relocatable schemas have no path of their own
+        let path = keys
+            .iter()
+            .find(|(key, kind, _)| key == ".path" && *kind == b'v')
+            .and_then(|(_, _, value)| Some(Variant::from_bytes(value.to_vec())?.as_str()?.to_owned()));
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let defaults = keys
+            .into_iter()
+            .filter(|(key, kind, _)| *kind == b'v' && !key.starts_with('.') && !key.ends_with('/'))
+            .map(|(key, _, value)| (format!("{path}{key}"), value))
+            .filter(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, value)| Some((key, Variant::from_bytes(value.to_vec())?.first_member()?)))
+            .collect();
+
+        schemas.insert(id, defaults);
+    }
+
+    Some(schemas)
+}
+
+fn read_schema_defaults(prefix: &str) -> Result<BTreeMap<String, Variant>, Box<dyn Error>> {
+    // Same precedence as GSettings, the first directory providing a schema wins
+    let mut dirs: Vec<PathBuf> = std::env::var_os("GSETTINGS_SCHEMA_DIR")
+        .map(|dirs| std::env::split_paths(&dirs).collect())
+        .unwrap_or_default();
+    dirs.extend(
+        std::iter::once(user_dir("XDG_DATA_HOME", ".local/share")?)
+            .chain(system_data_dirs())
+            .map(|dir| dir.join("glib-2.0").join("schemas")),
+    );
+
+    let mut seen = HashSet::new();
+    let mut defaults = BTreeMap::new();
+    for dir in dirs {
+        let data = match std::fs::read(dir.join("gschemas.compiled")) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        // GSettings skips caches it can not read as well
+        for (id, values) in parse_schemas(&data, prefix).unwrap_or_default() {
+            if seen.insert(id) {
+                defaults.extend(values);
+            }
+        }
+    }
+
+    Ok(defaults)
+}
+
+// The lowest database locking a key hides the values of all databases above it
+fn merge_values(mut values: BTreeMap<String, Variant>, databases: &[Database]) -> BTreeMap<String, Variant> {
+    let keys: BTreeSet<&String> = databases.iter().flat_map(|database| database.values.keys()).collect();
+    for key in keys {
+        let level = (1..databases.len())
+            .rev()
+            .find(|index| databases[*index].locks.contains(key))
+            .unwrap_or(0);
+
+        if let Some(value) = databases[level..].iter().find_map(|database| database.values.get(key)) {
+            values.insert(key.clone(), value.clone());
+        }
+    }
+
+    values
+}
+
+// Object path of the writer service for the user database on top of the profile
+pub fn writer_path() -> Result<String, Box<dyn Error>> {
+    match read_profile()?.first() {
+        Some(Source::User(name)) => Ok(format!("/ca/desrt/dconf/Writer/{name}")),
+        _ => Err("dconf profile has no writable database".into()),
+    }
+}
+
+// Returns the values set by user under prefix, keys at their default value are absent
+pub fn read_user_values(prefix: &str) -> Result<BTreeMap<String, Variant>, Box<dyn Error>> {
+    match read_profile()?.first() {
+        Some(source @ Source::User(_)) => Ok(read_database(&source.path()?, prefix)?.values),
+        _ => Ok(BTreeMap::new()),
+    }
+}
+
+// Returns the values applications see under prefix, schema defaults included
+pub fn read_values(prefix: &str) -> Result<BTreeMap<String, Variant>, Box<dyn Error>> {
+    let databases = read_profile()?
+        .iter()
+        .map(|source| read_database(&source.path()?, prefix))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(merge_values(read_schema_defaults(prefix)?, &databases))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::linux::{
+        network::dconf::{
+            merge_values, parse_database, parse_profile, parse_schemas, serialize_changeset, Database, Source, Variant,
+        },
+        testdata::TestData,
+    };
+
+    #[test]
+    pub fn test_variant() {
+        let strings = vec!["localhost".to_owned(), "*.lan".to_owned()];
+
+        assert_eq!(Variant::from("manual").as_str(), Some("manual"));
+        assert_eq!(Variant::from(7890).as_i32(), Some(7890));
+        assert_eq!(Variant::from(strings.as_slice()).as_strings(), Some(strings));
+        assert_eq!(Variant::from([].as_slice()).as_strings(), Some(vec![]));
+        assert_eq!(Variant::from(true).as_bytes(), b"\x01\0b");
+        assert_eq!(Variant::from(7890).as_str(), None);
+
+        assert_eq!(Variant::new(vec![1], "(b)").first_member(), Some(Variant::from(true)));
+        assert_eq!(
+            Variant::new(b"none\0\x05".to_vec(), "(s(yau))").first_member(),
+            Some(Variant::from("none"))
+        );
+        assert_eq!(Variant::new(vec![0; 8], "((ii)s)").first_member(), None);
+    }
+
+    #[test]
+    pub fn test_serialize_changeset() {
+        let changeset = serialize_changeset(&[("/a".to_owned(), Some(Variant::from(false))), ("/b".to_owned(), None)]);
+
+        assert_eq!(changeset, b"/a\0\0\0\0\0\0\0\0b\0\x03\0\0\0/b\0\0\0\0\0\0\x03\x0d\x19");
+    }
+
+    #[test]
+    pub fn test_parse_database() {
+        let data = TestData::get("dconf-user").unwrap().data;
+
+        let database = parse_database(&data, "/system/proxy/").unwrap();
+
+        assert_eq!(
+            database.values.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("/system/proxy/http/port".to_owned(), Variant::from(7890)),
+                (
+                    "/system/proxy/ignore-hosts".to_owned(),
+                    Variant::from(["a".to_owned()].as_slice())
+                ),
+                ("/system/proxy/mode".to_owned(), Variant::from("manual")),
+            ]
+        );
+        assert!(database.locks.is_empty());
+
+        let database = parse_database(&data, "/").unwrap();
+        assert_eq!(
+            database.values.get("/org/gnome/desktop/interface/gtk-theme"),
+            Some(&Variant::from("Foo"))
+        );
+
+        let data = TestData::get("dconf-system").unwrap().data;
+
+        let database = parse_database(&data, "/system/proxy/").unwrap();
+        assert_eq!(database.values.len(), 3);
+        assert_eq!(database.locks.into_iter().collect::<Vec<_>>(), vec!["/system/proxy/mode"]);
+
+        assert!(parse_database(b"GVariant", "/").is_none());
+        assert!(parse_database(&data[..64], "/").is_none());
+    }
+
+    #[test]
+    pub fn test_parse_profile() {
+        let profile = "\
+# Lock down proxy settings
+user-db:user
+system-db:local # site wide
+service-db:remote
+
+file-db:/opt/dconf/db
+";
+
+        assert_eq!(
+            parse_profile(profile),
+            vec![
+                Source::User("user".to_owned()),
+                Source::System(PathBuf::from("/etc/dconf/db/local")),
+                Source::System(PathBuf::from("/opt/dconf/db")),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_parse_schemas() {
+        let data = TestData::get("gschemas.compiled").unwrap().data;
+
+        let schemas = parse_schemas(&data, "/system/proxy/").unwrap();
+        assert_eq!(schemas.len(), 5);
+
+        let defaults = &schemas["org.gnome.system.proxy"];
+        assert_eq!(defaults["/system/proxy/mode"], Variant::from("none"));
+        assert_eq!(defaults["/system/proxy/use-same-proxy"], Variant::from(true));
+        // Overridden by vendor
+        assert_eq!(
+            defaults["/system/proxy/ignore-hosts"],
+            Variant::from(["localhost".to_owned(), "*.corp".to_owned()].as_slice())
+        );
+
+        let defaults = &schemas["org.gnome.system.proxy.http"];
+        assert_eq!(defaults["/system/proxy/http/port"], Variant::from(8080));
+        assert_eq!(defaults["/system/proxy/http/host"], Variant::from(""));
+
+        assert!(parse_schemas(&data, "/org/")
+            .unwrap()
+            .values()
+            .all(|defaults| defaults.is_empty()));
+    }
+
+    #[test]
+    pub fn test_merge_values() {
+        let defaults = parse_schemas(&TestData::get("gschemas.compiled").unwrap().data, "/system/proxy/")
+            .unwrap()
+            .into_values()
+            .flatten()
+            .collect();
+        let databases = [
+            parse_database(&TestData::get("dconf-user").unwrap().data, "/system/proxy/").unwrap(),
+            Database::default(),
+            parse_database(&TestData::get("dconf-system").unwrap().data, "/system/proxy/").unwrap(),
+        ];
+
+        let values = merge_values(defaults, &databases);
+
+        // Locked by the system database
+        assert_eq!(values["/system/proxy/mode"], Variant::from("auto"));
+        assert_eq!(values["/system/proxy/autoconfig-url"], Variant::from("http://wpad/wpad.dat"));
+        assert_eq!(
+            values["/system/proxy/ignore-hosts"],
+            Variant::from(["a".to_owned()].as_slice())
+        );
+        assert_eq!(values["/system/proxy/http/port"], Variant::from(7890));
+        assert_eq!(values["/system/proxy/http/host"], Variant::from(""));
+        assert_eq!(values["/system/proxy/use-same-proxy"], Variant::from(true));
+    }
+}
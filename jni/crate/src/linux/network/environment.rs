@@ -0,0 +1,176 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use futures::executor::block_on;
+use zbus::Connection;
+
+use crate::{
+    common::network::{ProxyEndpoint, SystemProxyConfig},
+    linux::{dbus::systemd_manager::ManagerProxy, file::write_file_atomically},
+};
+
+const VARIABLES: [&str; 4] = ["http_proxy", "https_proxy", "all_proxy", "no_proxy"];
+
+fn environment_path(app_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if app_id.is_empty() || app_id.starts_with('.') || app_id.contains('/') {
+        return Err(format!("invalid app id: {app_id}").into());
+    }
+
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home::home_dir().ok_or("home directory not found")?.join(".config"),
+    };
+
+    Ok(config_dir.join("environment.d").join(format!("{app_id}.conf")))
+}
+
+fn variable_names() -> Vec<String> {
+    VARIABLES
+        .iter()
+        .flat_map(|name| [name.to_string(), name.to_uppercase()])
+        .collect()
+}
+
+fn build_variables(config: &SystemProxyConfig) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    if config.http.is_none() && config.https.is_none() && config.socks.is_none() {
+        return Err("environment variables require a http, https or socks proxy".into());
+    }
+
+    let url = |scheme: &str, endpoint: &Option<ProxyEndpoint>| endpoint.as_ref().map(|e| format!("{scheme}://{e}"));
+
+    let values = [
+        url("http", &config.http),
+        url("http", &config.https),
+        url("socks5", &config.socks),
+        Some(super::translate_excludes(&config.excludes, ".").join(",")).filter(|excludes| !excludes.is_empty()),
+    ];
+
+    let mut variables = Vec::new();
+    for (name, value) in VARIABLES.iter().zip(values) {
+        if let Some(value) = value {
+            if value.contains(['\n', '\\', '$', '"', '\'']) {
+                return Err(format!("invalid value of {name}: {value}").into());
+            }
+
+            variables.push((name.to_string(), value.clone()));
+            variables.push((name.to_uppercase(), value));
+        }
+    }
+
+    Ok(variables)
+}
+
+fn format_environment(variables: &[(String, String)]) -> String {
+    let mut content = String::from("# Generated by clash-multiplatform-compat, changes will be overwritten\n");
+
+    for (name, value) in variables {
+        content.push_str(&format!("{name}={value}\n"));
+    }
+
+    content
+}
+
+async fn update_manager_environment(variables: &[(String, String)]) -> Result<(), Box<dyn Error>> {
+    let conn = Connection::session().await?;
+    let manager = ManagerProxy::new(&conn).await?;
+
+    let names = variable_names();
+    let names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+    let assignments = variables
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>();
+    let assignments = assignments.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+
+    manager.unset_and_set_environment(&names, &assignments).await?;
+
+    Ok(())
+}
+
+fn remove_environment_file(path: &Path) -> Result<(), Box<dyn Error>> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn set_proxy_environment(enabled: bool, app_id: &str, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let path = environment_path(app_id)?;
+
+    let variables = if enabled {
+        let variables = build_variables(config)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        write_file_atomically(&path, format_environment(&variables).as_bytes(), None)?;
+
+        variables
+    } else {
+        remove_environment_file(&path)?;
+
+        Vec::new()
+    };
+
+    block_on(update_manager_environment(&variables))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use crate::{
+        common::network::{ProxyEndpoint, SystemProxyConfig},
+        linux::network::environment::{build_variables, environment_path, format_environment, variable_names},
+    };
+
+    #[test]
+    pub fn test_build_variables() -> Result<(), Box<dyn Error>> {
+        let mut config = SystemProxyConfig {
+            http: Some(ProxyEndpoint {
+                host: "127.0.0.1".to_owned(),
+                port: 7890,
+            }),
+            https: Some(ProxyEndpoint {
+                host: "127.0.0.1".to_owned(),
+                port: 7890,
+            }),
+            ftp: None,
+            socks: Some(ProxyEndpoint {
+                host: "::1".to_owned(),
+                port: 7891,
+            }),
+            auto_config_url: None,
+            excludes: vec!["localhost".parse()?, "10.0.0.0/8".parse()?, "*.lan".parse()?],
+        };
+
+        assert_eq!(
+            format_environment(&build_variables(&config)?),
+            "\
+# Generated by clash-multiplatform-compat, changes will be overwritten
+http_proxy=http://127.0.0.1:7890
+HTTP_PROXY=http://127.0.0.1:7890
+https_proxy=http://127.0.0.1:7890
+HTTPS_PROXY=http://127.0.0.1:7890
+all_proxy=socks5://[::1]:7891
+ALL_PROXY=socks5://[::1]:7891
+no_proxy=localhost,10.0.0.0/8,.lan
+NO_PROXY=localhost,10.0.0.0/8,.lan
+"
+        );
+
+        config.http = None;
+        config.https = None;
+        config.socks = None;
+        assert!(build_variables(&config).is_err());
+
+        assert_eq!(variable_names().len(), 8);
+        assert!(environment_path("../escape").is_err());
+        assert!(environment_path("clash").unwrap().ends_with("environment.d/clash.conf"));
+
+        Ok(())
+    }
+}
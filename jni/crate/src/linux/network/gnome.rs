@@ -0,0 +1,439 @@
+use std::{collections::BTreeMap, error::Error};
+
+use futures::{executor::block_on, future, StreamExt};
+use zbus::Connection;
+
+use crate::{
+    common::network::{Holder, ProxyEndpoint, ProxyMode, SystemProxyConfig, SystemProxySettings},
+    linux::{
+        dbus::dconf_writer::{NotifyStream, WriterProxy},
+        network::dconf::{self, Variant},
+    },
+};
+
+const PROXY_DIR: &str = "/system/proxy/";
+
+fn parse_settings(values: &BTreeMap<String, Variant>) -> SystemProxySettings {
+    let value = |key: &str| values.get(&format!("{PROXY_DIR}{key}"));
+    let string = |key: &str| value(key).and_then(Variant::as_str).map(str::to_owned);
+
+    let endpoint = |protocol: &str| {
+        let host = string(&format!("{protocol}/host")).filter(|host| !host.is_empty())?;
+        let port = value(&format!("{protocol}/port"))
+            .and_then(Variant::as_i32)
+            .and_then(|port| u16::try_from(port).ok())
+            .filter(|port| *port != 0)?;
+
+        Some(ProxyEndpoint { host, port })
+    };
+
+    let mode = match string("mode").as_deref() {
+        Some("manual") => ProxyMode::Manual,
+        Some("auto") => ProxyMode::Auto,
+        _ => ProxyMode::None,
+    };
+
+    SystemProxySettings {
+        mode,
+        http: endpoint("http"),
+        https: endpoint("https"),
+        ftp: endpoint("ftp"),
+        socks: endpoint("socks"),
+        auto_config_url: string("autoconfig-url").filter(|url| !url.is_empty()),
+        excludes: value("ignore-hosts").and_then(Variant::as_strings).unwrap_or_default(),
+    }
+}
+
+fn read_settings() -> Result<BTreeMap<String, Variant>, Box<dyn Error>> {
+    dconf::read_values(PROXY_DIR)
+}
+
+pub fn get_system_proxy() -> Result<SystemProxySettings, Box<dyn Error>> {
+    Ok(parse_settings(&read_settings()?))
+}
+
+// Snapshot is one `<path> <value>` line per key set by user, value is the hex encoded serialized variant
+fn format_snapshot(values: &BTreeMap<String, Variant>) -> String {
+    values
+        .iter()
+        .map(|(path, value)| {
+            let value: String = value.as_bytes().iter().map(|b| format!("{b:02x}")).collect();
+
+            format!("{path} {value}\n")
+        })
+        .collect()
+}
+
+pub fn snapshot_system_proxy() -> Result<String, Box<dyn Error>> {
+    Ok(format_snapshot(&dconf::read_user_values(PROXY_DIR)?))
+}
+
+fn parse_snapshot(snapshot: &str) -> Result<BTreeMap<String, Variant>, Box<dyn Error>> {
+    snapshot
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (path, value) = line.split_once(' ').ok_or("invalid snapshot")?;
+            if !path.starts_with(PROXY_DIR) {
+                return Err(format!("unknown key: {path}").into());
+            }
+
+            let value = value.trim();
+            let bytes = (0..value.len())
+                .step_by(2)
+                .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()
+                .and_then(Variant::from_bytes)
+                .ok_or_else(|| format!("invalid value: {line}"))?;
+
+            Ok((path.to_owned(), bytes))
+        })
+        .collect()
+}
+
+async fn dconf_writer(conn: &Connection) -> Result<WriterProxy<'static>, Box<dyn Error>> {
+    Ok(WriterProxy::builder(conn)
+        .destination("ca.desrt.dconf")?
+        .path(dconf::writer_path()?)?
+        .build()
+        .await?)
+}
+
+async fn write_dconf(changes: &[(String, Option<Variant>)]) -> Result<(), Box<dyn Error>> {
+    // All keys of the changeset are applied in a single transaction
+    let blob = dconf::serialize_changeset(changes);
+
+    let conn = Connection::session().await?;
+
+    dconf_writer(&conn).await?.change(&blob).await?;
+
+    Ok(())
+}
+
+// Keys absent from snapshot were at their default value, reset them instead of keeping ours
+fn restore_changes(snapshot: BTreeMap<String, Variant>, current: &BTreeMap<String, Variant>) -> Vec<(String, Option<Variant>)> {
+    let resets: Vec<(String, Option<Variant>)> = current
+        .keys()
+        .filter(|path| !snapshot.contains_key(*path))
+        .map(|path| (path.clone(), None))
+        .collect();
+
+    snapshot
+        .into_iter()
+        .map(|(path, value)| (path, Some(value)))
+        .chain(resets)
+        .collect()
+}
+
+pub fn restore_system_proxy(snapshot: &str) -> Result<(), Box<dyn Error>> {
+    let changes = restore_changes(parse_snapshot(snapshot)?, &dconf::read_user_values(PROXY_DIR)?);
+
+    block_on(write_dconf(&changes))
+}
+
+fn endpoint_changes(protocol: &str, endpoint: &Option<ProxyEndpoint>) -> [(String, Option<Variant>); 2] {
+    let (host, port) = match endpoint {
+        Some(endpoint) => (endpoint.host.as_str(), endpoint.port as i32),
+        None => ("", 0),
+    };
+
+    [
+        (format!("/system/proxy/{protocol}/host"), Some(Variant::from(host))),
+        (format!("/system/proxy/{protocol}/port"), Some(Variant::from(port))),
+    ]
+}
+
+fn config_changes(config: &SystemProxyConfig) -> Vec<(String, Option<Variant>)> {
+    let excludes = super::translate_excludes(&config.excludes, "*.");
+
+    if let Some(url) = &config.auto_config_url {
+        return vec![
+            ("/system/proxy/mode".to_owned(), Some(Variant::from("auto"))),
+            ("/system/proxy/autoconfig-url".to_owned(), Some(Variant::from(url.as_str()))),
+            (
+                "/system/proxy/ignore-hosts".to_owned(),
+                Some(Variant::from(excludes.as_slice())),
+            ),
+        ];
+    }
+
+    let mut changes = vec![
+        ("/system/proxy/mode".to_owned(), Some(Variant::from("manual"))),
+        ("/system/proxy/autoconfig-url".to_owned(), Some(Variant::from(""))),
+        ("/system/proxy/use-same-proxy".to_owned(), Some(Variant::from(false))),
+        (
+            "/system/proxy/ignore-hosts".to_owned(),
+            Some(Variant::from(excludes.as_slice())),
+        ),
+        (
+            "/system/proxy/http/enabled".to_owned(),
+            Some(Variant::from(config.http.is_some())),
+        ),
+    ];
+
+    changes.extend(endpoint_changes("http", &config.http));
+    changes.extend(endpoint_changes("https", &config.https));
+    changes.extend(endpoint_changes("ftp", &config.ftp));
+    changes.extend(endpoint_changes("socks", &config.socks));
+
+    changes
+}
+
+pub fn set_system_proxy(enabled: bool, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let changes = if enabled {
+        config_changes(config)
+    } else {
+        vec![("/system/proxy/mode".to_owned(), Some(Variant::from("none")))]
+    };
+
+    block_on(write_dconf(&changes))
+}
+
+// Notified paths inside or above the proxy directory, a path ending with `/` covers the whole directory
+fn proxy_paths(prefix: &str, changes: &[&str]) -> Vec<String> {
+    changes
+        .iter()
+        .map(|change| format!("{prefix}{change}"))
+        .filter(|path| path.starts_with(PROXY_DIR) || PROXY_DIR.starts_with(path.as_str()))
+        .collect()
+}
+
+fn is_changed(paths: &[String], last: &BTreeMap<String, Variant>, current: &BTreeMap<String, Variant>) -> bool {
+    let values = |values: &BTreeMap<String, Variant>, path: &str| {
+        values
+            .iter()
+            .filter(|(key, _)| {
+                if path.ends_with('/') {
+                    key.starts_with(path)
+                } else {
+                    *key == path
+                }
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    paths.iter().any(|path| values(last, path) != values(current, path))
+}
+
+pub fn watch_system_proxy(on_changed: impl Fn() + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let stream: NotifyStream = block_on(async {
+        let conn = Connection::session().await?;
+        let writer = dconf_writer(&conn).await?;
+
+        // Make sure the writer service is running, otherwise no owner is there to send signals
+        writer.init().await?;
+
+        Ok(writer.receive_notify().await?) as Result<NotifyStream, Box<dyn Error>>
+    })?;
+
+    let paths = stream.filter_map(|signal| {
+        let paths = signal.args().map(|args| proxy_paths(args.prefix, &args.changes));
+
+        future::ready(paths.ok().filter(|paths| !paths.is_empty()))
+    });
+
+    // Writes of unchanged values are notified too, only report keys that really changed
+    let mut last = read_settings().unwrap_or_default();
+
+    super::watch_stream(paths.boxed(), move |paths| {
+        if let Ok(current) = read_settings() {
+            if is_changed(&paths, &last, &current) {
+                on_changed();
+            }
+
+            last = current;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, error::Error};
+
+    use crate::{
+        common::network::{ProxyEndpoint, ProxyExclude, ProxyMode, SystemProxyConfig, SystemProxySettings},
+        linux::network::{
+            dconf::Variant,
+            gnome::{config_changes, format_snapshot, is_changed, parse_settings, parse_snapshot, proxy_paths, restore_changes},
+        },
+    };
+
+    #[test]
+    pub fn test_parse_settings() {
+        let values: BTreeMap<String, Variant> = [
+            ("/system/proxy/mode", Variant::from("manual")),
+            (
+                "/system/proxy/ignore-hosts",
+                Variant::from(["localhost".to_owned(), "127.0.0.0/8".to_owned(), "::1".to_owned()].as_slice()),
+            ),
+            ("/system/proxy/http/host", Variant::from("127.0.0.1")),
+            ("/system/proxy/http/port", Variant::from(7890)),
+            ("/system/proxy/https/host", Variant::from("127.0.0.1")),
+            ("/system/proxy/https/port", Variant::from(7890)),
+            ("/system/proxy/ftp/host", Variant::from("")),
+            ("/system/proxy/socks/host", Variant::from("::1")),
+            ("/system/proxy/socks/port", Variant::from(7891)),
+        ]
+        .into_iter()
+        .map(|(path, value)| (path.to_owned(), value))
+        .collect();
+
+        assert_eq!(
+            parse_settings(&values),
+            SystemProxySettings {
+                mode: ProxyMode::Manual,
+                http: Some(ProxyEndpoint {
+                    host: "127.0.0.1".to_owned(),
+                    port: 7890,
+                }),
+                https: Some(ProxyEndpoint {
+                    host: "127.0.0.1".to_owned(),
+                    port: 7890,
+                }),
+                ftp: None,
+                socks: Some(ProxyEndpoint {
+                    host: "::1".to_owned(),
+                    port: 7891,
+                }),
+                auto_config_url: None,
+                excludes: vec!["localhost".to_owned(), "127.0.0.0/8".to_owned(), "::1".to_owned()],
+            }
+        );
+
+        let settings = parse_settings(&BTreeMap::new());
+        assert_eq!(settings.mode, ProxyMode::None);
+        assert!(settings.excludes.is_empty());
+    }
+
+    #[test]
+    pub fn test_parse_snapshot() -> Result<(), Box<dyn Error>> {
+        let values: BTreeMap<String, Variant> = [
+            (
+                "/system/proxy/ignore-hosts",
+                Variant::from(["localhost".to_owned(), "10.0.0.0/8".to_owned()].as_slice()),
+            ),
+            ("/system/proxy/mode", Variant::from("manual")),
+            ("/system/proxy/use-same-proxy", Variant::from(true)),
+            ("/system/proxy/http/port", Variant::from(3128)),
+            // Types unknown to us are kept as is
+            (
+                "/system/proxy/socks/port",
+                Variant::from_bytes(b"\x38\x04\0\0\0u".to_vec()).unwrap(),
+            ),
+        ]
+        .into_iter()
+        .map(|(path, value)| (path.to_owned(), value))
+        .collect();
+
+        assert_eq!(parse_snapshot(&format_snapshot(&values))?, values);
+
+        let current: BTreeMap<String, Variant> = [
+            ("/system/proxy/mode", Variant::from("manual")),
+            ("/system/proxy/http/host", Variant::from("127.0.0.1")),
+        ]
+        .into_iter()
+        .map(|(path, value)| (path.to_owned(), value))
+        .collect();
+
+        let changes = restore_changes(values.clone(), &current);
+        assert_eq!(changes.len(), values.len() + 1);
+        assert!(changes.contains(&("/system/proxy/mode".to_owned(), Some(Variant::from("manual")))));
+        assert!(changes.contains(&("/system/proxy/http/host".to_owned(), None)));
+
+        assert!(parse_snapshot("/system/proxy/mode 0").is_err());
+        assert!(parse_snapshot("/org/gnome/desktop/interface/gtk-theme 00").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_config_changes() {
+        let changes = config_changes(&SystemProxyConfig {
+            http: Some(ProxyEndpoint {
+                host: "proxy.lan".to_owned(),
+                port: 7890,
+            }),
+            https: None,
+            ftp: None,
+            socks: Some(ProxyEndpoint {
+                host: "::1".to_owned(),
+                port: 7891,
+            }),
+            auto_config_url: None,
+            excludes: vec![ProxyExclude::Local, ProxyExclude::Wildcard("lan".to_owned())],
+        });
+
+        let value = |path: &str| changes.iter().find(|(p, _)| p == path).and_then(|(_, v)| v.clone());
+
+        assert_eq!(value("/system/proxy/mode"), Some(Variant::from("manual")));
+        assert_eq!(value("/system/proxy/http/host"), Some(Variant::from("proxy.lan")));
+        assert_eq!(value("/system/proxy/http/port"), Some(Variant::from(7890)));
+        assert_eq!(value("/system/proxy/https/host"), Some(Variant::from("")));
+        assert_eq!(value("/system/proxy/https/port"), Some(Variant::from(0)));
+        assert_eq!(value("/system/proxy/socks/host"), Some(Variant::from("::1")));
+        assert_eq!(value("/system/proxy/socks/port"), Some(Variant::from(7891)));
+        assert_eq!(
+            value("/system/proxy/ignore-hosts"),
+            Some(Variant::from(
+                [
+                    "localhost".to_owned(),
+                    "127.0.0.0/8".to_owned(),
+                    "::1".to_owned(),
+                    "*.lan".to_owned()
+                ]
+                .as_slice()
+            ))
+        );
+
+        let changes = config_changes(&SystemProxyConfig {
+            http: None,
+            https: None,
+            ftp: None,
+            socks: None,
+            auto_config_url: Some("http://127.0.0.1:7890/proxy.pac".to_owned()),
+            excludes: vec![],
+        });
+
+        let value = |path: &str| changes.iter().find(|(p, _)| p == path).and_then(|(_, v)| v.clone());
+
+        assert_eq!(value("/system/proxy/mode"), Some(Variant::from("auto")));
+        assert_eq!(
+            value("/system/proxy/autoconfig-url"),
+            Some(Variant::from("http://127.0.0.1:7890/proxy.pac"))
+        );
+    }
+
+    #[test]
+    pub fn test_proxy_paths() {
+        assert_eq!(proxy_paths("/system/proxy/mode", &[""]), vec!["/system/proxy/mode"]);
+        assert_eq!(
+            proxy_paths("/system/proxy/", &["http/host", "http/port"]),
+            vec!["/system/proxy/http/host", "/system/proxy/http/port"]
+        );
+        assert_eq!(proxy_paths("/system/", &[""]), vec!["/system/"]);
+        assert_eq!(
+            proxy_paths("/", &["org/gnome/", "system/proxy/mode"]),
+            vec!["/system/proxy/mode"]
+        );
+        assert!(proxy_paths("/org/gnome/desktop/interface/", &["gtk-theme"]).is_empty());
+    }
+
+    #[test]
+    pub fn test_is_changed() {
+        let last: BTreeMap<String, Variant> = [
+            ("/system/proxy/mode".to_owned(), Variant::from("manual")),
+            ("/system/proxy/http/port".to_owned(), Variant::from(7890)),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut current = last.clone();
+        current.insert("/system/proxy/http/port".to_owned(), Variant::from(7891));
+
+        assert!(!is_changed(&["/system/proxy/mode".to_owned()], &last, &current));
+        assert!(!is_changed(&["/system/proxy/http/port".to_owned()], &last, &last));
+        assert!(is_changed(&["/system/proxy/http/port".to_owned()], &last, &current));
+        assert!(is_changed(&["/system/".to_owned()], &last, &current));
+    }
+}
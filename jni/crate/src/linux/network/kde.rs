@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use futures::executor::block_on;
+use zbus::{names::BusName, Connection};
+
+use crate::{
+    common::{
+        network::{Holder, ProxyEndpoint, ProxyMode, SystemProxyConfig, SystemProxySettings},
+        watcher::{Watch, EVENT_CREATE, EVENT_DELETE, EVENT_MODIFY, EVENT_MOVE},
+    },
+    linux::{file::write_file_atomically, watcher::add_watcher},
+};
+
+const PROXY_GROUP: &str = "Proxy Settings";
+
+// ProxyType values used by KIO
+const PROXY_TYPE_NONE: &str = "0";
+const PROXY_TYPE_MANUAL: &str = "1";
+const PROXY_TYPE_PAC: &str = "2";
+const PROXY_TYPE_WPAD: &str = "3";
+const PROXY_TYPE_ENVIRONMENT: &str = "4";
+
+fn read_kioslaverc(path: &Path) -> Result<String, Box<dyn Error>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn kioslaverc_path() -> Result<PathBuf, Box<dyn Error>> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home::home_dir().ok_or("home directory not found")?.join(".config"),
+    };
+
+    Ok(config_dir.join("kioslaverc"))
+}
+
+fn format_proxy_url(scheme: &str, endpoint: &Option<ProxyEndpoint>) -> String {
+    match endpoint {
+        Some(ProxyEndpoint { host, port }) if host.contains(':') => format!("{scheme}://[{host}] {port}"),
+        Some(ProxyEndpoint { host, port }) => format!("{scheme}://{host} {port}"),
+        None => String::new(),
+    }
+}
+
+fn group_of(line: &str) -> Option<&str> {
+    let line = line.trim();
+
+    line.strip_prefix('[')?.strip_suffix(']')
+}
+
+fn key_of(line: &str) -> Option<&str> {
+    let (key, _) = line.split_once('=')?;
+
+    // Strip KConfig flags such as `key[$e]`
+    Some(key.split('[').next().unwrap_or(key).trim())
+}
+
+fn read_group(content: &str, group: &str) -> HashMap<String, String> {
+    let mut in_group = false;
+    let mut entries = HashMap::new();
+
+    for line in content.lines() {
+        if let Some(name) = group_of(line) {
+            in_group = name == group;
+        } else if in_group {
+            if let (Some(key), Some((_, value))) = (key_of(line), line.split_once('=')) {
+                entries.insert(key.to_owned(), value.trim().to_owned());
+            }
+        }
+    }
+
+    entries
+}
+
+fn parse_proxy_url(url: &str) -> Option<ProxyEndpoint> {
+    let url = url.trim();
+    let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let url = url.split_once('@').map(|(_, rest)| rest).unwrap_or(url);
+    let url = url.trim_end_matches('/');
+
+    let (host, port) = match url.split_once(' ') {
+        Some((host, port)) => (host, port.trim()),
+        None => url.rsplit_once(':').filter(|(host, _)| !host.ends_with(':'))?,
+    };
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return None;
+    }
+
+    Some(ProxyEndpoint {
+        host: host.to_owned(),
+        port: port.parse::<u16>().ok().filter(|port| *port != 0)?,
+    })
+}
+
+fn parse_settings(content: &str) -> SystemProxySettings {
+    let entries = read_group(content, PROXY_GROUP);
+    let proxy_type = entries.get("ProxyType").map(|s| s.as_str()).unwrap_or(PROXY_TYPE_NONE);
+
+    let endpoint = |key: &str| {
+        let value = entries.get(key)?;
+
+        if proxy_type == PROXY_TYPE_ENVIRONMENT {
+            parse_proxy_url(&std::env::var(value).ok()?)
+        } else {
+            parse_proxy_url(value)
+        }
+    };
+
+    let mode = match proxy_type {
+        PROXY_TYPE_MANUAL | PROXY_TYPE_ENVIRONMENT => ProxyMode::Manual,
+        PROXY_TYPE_PAC | PROXY_TYPE_WPAD => ProxyMode::Auto,
+        _ => ProxyMode::None,
+    };
+
+    SystemProxySettings {
+        mode,
+        http: endpoint("httpProxy"),
+        https: endpoint("httpsProxy"),
+        ftp: endpoint("ftpProxy"),
+        socks: endpoint("socksProxy"),
+        auto_config_url: entries.get("Proxy Config Script").filter(|url| !url.is_empty()).cloned(),
+        excludes: entries
+            .get("NoProxyFor")
+            .map(|hosts| {
+                hosts
+                    .split(',')
+                    .map(|host| host.trim())
+                    .filter(|host| !host.is_empty())
+                    .map(|host| host.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn update_group(content: &str, group: &str, entries: &[(&str, String)]) -> String {
+    let mut written = vec![false; entries.len()];
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_group = false;
+    let mut group_found = false;
+
+    let flush = |lines: &mut Vec<String>, written: &mut Vec<bool>| {
+        let insert_at = lines
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        for (index, (key, value)) in entries.iter().enumerate().rev() {
+            if !written[index] {
+                lines.insert(insert_at, format!("{key}={value}"));
+                written[index] = true;
+            }
+        }
+    };
+
+    for line in content.lines() {
+        if let Some(name) = group_of(line) {
+            if in_group {
+                flush(&mut lines, &mut written);
+            }
+
+            in_group = name == group;
+            group_found |= in_group;
+        } else if in_group {
+            if let Some(index) = key_of(line).and_then(|key| entries.iter().position(|(k, _)| *k == key)) {
+                if !written[index] {
+                    lines.push(format!("{}={}", entries[index].0, entries[index].1));
+                    written[index] = true;
+                }
+
+                continue;
+            }
+        }
+
+        lines.push(line.to_owned());
+    }
+
+    if in_group {
+        flush(&mut lines, &mut written);
+    }
+
+    if !group_found {
+        if lines.last().map(|line| !line.trim().is_empty()).unwrap_or(false) {
+            lines.push(String::new());
+        }
+
+        lines.push(format!("[{group}]"));
+        flush(&mut lines, &mut written);
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn replace_group(content: &str, group: &str, body: &[&str]) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_group = false;
+    let mut replaced = false;
+
+    for line in content.lines() {
+        if let Some(name) = group_of(line) {
+            in_group = name == group;
+            lines.push(line);
+
+            if in_group && !replaced {
+                lines.extend_from_slice(body);
+                replaced = true;
+            }
+        } else if !in_group || line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+
+    let header = format!("[{group}]");
+    if !replaced {
+        if lines.last().map(|line| !line.trim().is_empty()).unwrap_or(false) {
+            lines.push("");
+        }
+
+        lines.push(&header);
+        lines.extend_from_slice(body);
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn group_body<'a>(content: &'a str, group: &str) -> Vec<&'a str> {
+    let mut in_group = false;
+    let mut body = Vec::new();
+
+    for line in content.lines() {
+        if let Some(name) = group_of(line) {
+            in_group = name == group;
+        } else if in_group && !line.trim().is_empty() {
+            body.push(line);
+        }
+    }
+
+    body
+}
+
+async fn reparse_slave_configuration() -> Result<(), Box<dyn Error>> {
+    let conn = Connection::session().await?;
+
+    conn.emit_signal(
+        None::<BusName>,
+        "/KIO/Scheduler",
+        "org.kde.KIO.Scheduler",
+        "reparseSlaveConfiguration",
+        &"",
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub fn set_system_proxy(enabled: bool, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let entries = if let (true, Some(url)) = (enabled, &config.auto_config_url) {
+        vec![("ProxyType", PROXY_TYPE_PAC.to_owned()), ("Proxy Config Script", url.clone())]
+    } else if enabled {
+        vec![
+            ("ProxyType", PROXY_TYPE_MANUAL.to_owned()),
+            ("Proxy Config Script", String::new()),
+            ("httpProxy", format_proxy_url("http", &config.http)),
+            ("httpsProxy", format_proxy_url("http", &config.https)),
+            ("ftpProxy", format_proxy_url("http", &config.ftp)),
+            ("socksProxy", format_proxy_url("socks", &config.socks)),
+            ("NoProxyFor", super::translate_excludes(&config.excludes, ".").join(",")),
+            ("ReversedException", "false".to_owned()),
+        ]
+    } else {
+        vec![("ProxyType", PROXY_TYPE_NONE.to_owned())]
+    };
+
+    let path = kioslaverc_path()?;
+    let content = read_kioslaverc(&path)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    write_file_atomically(&path, update_group(&content, PROXY_GROUP, &entries).as_bytes(), None)?;
+
+    block_on(reparse_slave_configuration())
+}
+
+pub fn get_system_proxy() -> Result<SystemProxySettings, Box<dyn Error>> {
+    Ok(parse_settings(&read_kioslaverc(&kioslaverc_path()?)?))
+}
+
+pub fn snapshot_system_proxy() -> Result<String, Box<dyn Error>> {
+    Ok(group_body(&read_kioslaverc(&kioslaverc_path()?)?, PROXY_GROUP).join("\n"))
+}
+
+pub fn restore_system_proxy(snapshot: &str) -> Result<(), Box<dyn Error>> {
+    let body = snapshot.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>();
+
+    let path = kioslaverc_path()?;
+    let content = read_kioslaverc(&path)?;
+
+    write_file_atomically(&path, replace_group(&content, PROXY_GROUP, &body).as_bytes(), None)?;
+
+    block_on(reparse_slave_configuration())
+}
+
+pub fn watch_system_proxy(on_changed: impl Fn() + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let path = kioslaverc_path()?;
+    let config_dir = path.parent().ok_or("invalid kioslaverc path")?;
+
+    // kioslaverc is usually replaced by rename, so watch the directory instead of the file
+    std::fs::create_dir_all(config_dir)?;
+
+    struct ListenerImpl<F> {
+        path: PathBuf,
+        on_changed: F,
+    }
+
+    impl<F: Fn()> crate::common::watcher::Listener for ListenerImpl<F> {
+        fn on_changed(&self, path: &[u8], _: u32) {
+            if Path::new(OsStr::from_bytes(path)) == self.path {
+                (self.on_changed)();
+            }
+        }
+    }
+
+    let watcher = add_watcher(
+        &[Watch {
+            path: config_dir.to_str().ok_or("invalid config directory")?.to_owned(),
+            events: EVENT_CREATE | EVENT_MODIFY | EVENT_DELETE | EVENT_MOVE,
+        }],
+        ListenerImpl { path, on_changed },
+    )?;
+
+    struct HolderImpl {
+        _watcher: Box<dyn crate::common::watcher::Holder>,
+    }
+
+    impl Holder for HolderImpl {}
+
+    Ok(Box::new(HolderImpl { _watcher: watcher }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::network::{ProxyEndpoint, ProxyMode, SystemProxySettings},
+        linux::network::kde::{
+            format_proxy_url, group_body, parse_proxy_url, parse_settings, replace_group, update_group, PROXY_GROUP,
+        },
+    };
+
+    #[test]
+    pub fn test_parse_settings() {
+        let content = "\
+[Proxy Settings]
+NoProxyFor=localhost, 127.0.0.1
+Proxy Config Script=
+ProxyType=1
+ReversedException=false
+httpProxy=http://127.0.0.1 7890
+httpsProxy=http://127.0.0.1:7890
+socksProxy=socks://[::1] 7891
+ftpProxy=
+";
+
+        assert_eq!(
+            parse_settings(content),
+            SystemProxySettings {
+                mode: ProxyMode::Manual,
+                http: Some(ProxyEndpoint {
+                    host: "127.0.0.1".to_owned(),
+                    port: 7890,
+                }),
+                https: Some(ProxyEndpoint {
+                    host: "127.0.0.1".to_owned(),
+                    port: 7890,
+                }),
+                ftp: None,
+                socks: Some(ProxyEndpoint {
+                    host: "::1".to_owned(),
+                    port: 7891,
+                }),
+                auto_config_url: None,
+                excludes: vec!["localhost".to_owned(), "127.0.0.1".to_owned()],
+            }
+        );
+
+        assert_eq!(parse_settings("").mode, ProxyMode::None);
+        assert_eq!(parse_proxy_url("http://user@proxy.lan:3128/").unwrap().host, "proxy.lan");
+        assert_eq!(parse_proxy_url("http://::1"), None);
+
+        let endpoint = Some(ProxyEndpoint {
+            host: "::1".to_owned(),
+            port: 7891,
+        });
+        assert_eq!(format_proxy_url("socks", &endpoint), "socks://[::1] 7891");
+        assert_eq!(parse_proxy_url(&format_proxy_url("socks", &endpoint)), endpoint);
+        assert_eq!(format_proxy_url("http", &None), "");
+    }
+
+    #[test]
+    pub fn test_update_group() {
+        let content = "\
+[$Version]
+update_info=kioslave.upd:proxy-type
+
+[Proxy Settings]
+ProxyType=0
+httpProxy=http://192.168.1.1 3128
+ftpProxy=
+
+[Cache Settings]
+MaxCacheSize=51200
+";
+
+        let updated = update_group(
+            content,
+            PROXY_GROUP,
+            &[
+                ("ProxyType", "1".to_owned()),
+                ("httpProxy", "http://127.0.0.1 7890".to_owned()),
+                ("NoProxyFor", "localhost,127.0.0.1".to_owned()),
+            ],
+        );
+
+        assert_eq!(
+            updated,
+            "\
+[$Version]
+update_info=kioslave.upd:proxy-type
+
+[Proxy Settings]
+ProxyType=1
+httpProxy=http://127.0.0.1 7890
+ftpProxy=
+NoProxyFor=localhost,127.0.0.1
+
+[Cache Settings]
+MaxCacheSize=51200
+"
+        );
+
+        assert_eq!(
+            update_group("", PROXY_GROUP, &[("ProxyType", "0".to_owned())]),
+            "[Proxy Settings]\nProxyType=0\n"
+        );
+
+        assert_eq!(
+            update_group("[General]\nkey=value\n", PROXY_GROUP, &[("ProxyType", "0".to_owned())]),
+            "[General]\nkey=value\n\n[Proxy Settings]\nProxyType=0\n"
+        );
+    }
+
+    #[test]
+    pub fn test_replace_group() {
+        let original = "\
+[Proxy Settings]
+NoProxyFor=*.corp.example
+ProxyType[$e]=1
+httpProxy=http://proxy.corp.example 3128
+
+[Cache Settings]
+MaxCacheSize=51200
+";
+
+        let snapshot = group_body(original, PROXY_GROUP);
+        assert_eq!(
+            snapshot,
+            vec![
+                "NoProxyFor=*.corp.example",
+                "ProxyType[$e]=1",
+                "httpProxy=http://proxy.corp.example 3128"
+            ]
+        );
+
+        let modified = update_group(
+            original,
+            PROXY_GROUP,
+            &[
+                ("ProxyType", "1".to_owned()),
+                ("httpProxy", "http://127.0.0.1 7890".to_owned()),
+                ("ReversedException", "false".to_owned()),
+            ],
+        );
+        assert_ne!(modified, original);
+
+        assert_eq!(replace_group(&modified, PROXY_GROUP, &snapshot), original);
+
+        assert_eq!(
+            replace_group("[General]\nkey=value\n", PROXY_GROUP, &[]),
+            "[General]\nkey=value\n\n[Proxy Settings]\n"
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use futures::{executor::ThreadPool, future::RemoteHandle, stream::BoxStream, task::SpawnExt, StreamExt};
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::network::{Holder, Listener, ProxyExclude, ProxyMode, SystemProxyConfig, SystemProxySettings},
+    linux::file::write_file_atomically,
+};
+
+mod dconf;
+mod environment;
+mod gnome;
+mod kde;
+mod networkmanager;
+mod resolved;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Backend {
+    Gnome,
+    Kde,
+    NetworkManager,
+}
+
+impl Backend {
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::Gnome => "gnome",
+            Backend::Kde => "kde",
+            Backend::NetworkManager => "networkmanager",
+        }
+    }
+
+    // Connection profiles of NetworkManager can only point to a pac script
+    fn supports(&self, config: &SystemProxyConfig) -> bool {
+        *self != Backend::NetworkManager || config.auto_config_url.is_some()
+    }
+
+    fn get(&self) -> Result<SystemProxySettings, Box<dyn Error>> {
+        match self {
+            Backend::Gnome => gnome::get_system_proxy(),
+            Backend::Kde => kde::get_system_proxy(),
+            Backend::NetworkManager => networkmanager::get_system_proxy(),
+        }
+    }
+
+    fn set(&self, enabled: bool, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+        match self {
+            Backend::Gnome => gnome::set_system_proxy(enabled, config),
+            Backend::Kde => kde::set_system_proxy(enabled, config),
+            Backend::NetworkManager => networkmanager::set_system_proxy(enabled, config),
+        }
+    }
+
+    fn snapshot(&self) -> Result<String, Box<dyn Error>> {
+        match self {
+            Backend::Gnome => gnome::snapshot_system_proxy(),
+            Backend::Kde => kde::snapshot_system_proxy(),
+            Backend::NetworkManager => networkmanager::snapshot_system_proxy(),
+        }
+    }
+
+    fn restore(&self, snapshot: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            Backend::Gnome => gnome::restore_system_proxy(snapshot),
+            Backend::Kde => kde::restore_system_proxy(snapshot),
+            Backend::NetworkManager => networkmanager::restore_system_proxy(snapshot),
+        }
+    }
+
+    fn watch(&self, on_changed: impl Fn() + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+        match self {
+            Backend::Gnome => gnome::watch_system_proxy(on_changed),
+            Backend::Kde => kde::watch_system_proxy(on_changed),
+            Backend::NetworkManager => networkmanager::watch_system_proxy(on_changed),
+        }
+    }
+}
+
+// XDG_CURRENT_DESKTOP is a colon separated list such as `ubuntu:GNOME`, XDG_SESSION_DESKTOP names the session
+// (gnome, plasma, plasmawayland, ...) and is only consulted when the former is missing
+fn detect_desktop(current_desktop: Option<&str>, session_desktop: Option<&str>) -> Option<Backend> {
+    if let Some(current_desktop) = current_desktop.filter(|desktops| !desktops.is_empty()) {
+        return current_desktop.split(':').find_map(|desktop| match desktop {
+            "GNOME" => Some(Backend::Gnome),
+            "KDE" => Some(Backend::Kde),
+            _ => None,
+        });
+    }
+
+    match session_desktop?.to_lowercase().as_str() {
+        "gnome" | "gnome-xorg" | "gnome-wayland" => Some(Backend::Gnome),
+        "kde" | "plasma" | "plasmawayland" | "plasmax11" => Some(Backend::Kde),
+        _ => None,
+    }
+}
+
+fn desktop_backend() -> Option<Backend> {
+    detect_desktop(
+        std::env::var("XDG_CURRENT_DESKTOP").ok().as_deref(),
+        std::env::var("XDG_SESSION_DESKTOP").ok().as_deref(),
+    )
+}
+
+// Desktop settings only reach applications of the session, per connection proxy of NetworkManager covers the
+// rest of the system, so both are applied when available. Desktop comes first and is the one read back.
+fn current_backends() -> Vec<Backend> {
+    let mut backends: Vec<Backend> = desktop_backend().into_iter().collect();
+    if networkmanager::is_available() {
+        backends.push(Backend::NetworkManager);
+    }
+
+    backends
+}
+
+fn primary_backend() -> Option<Backend> {
+    desktop_backend().or_else(|| networkmanager::is_available().then_some(Backend::NetworkManager))
+}
+
+static POOL: Lazy<ThreadPool> = Lazy::new(|| ThreadPool::new().unwrap());
+
+// Listener may issue blocking dbus calls, which must not happen on executor threads, events are handled on a thread
+fn watch_stream<T: Send + 'static>(
+    mut stream: BoxStream<'static, T>,
+    mut on_event: impl FnMut(T) + Send + 'static,
+) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let (sender, receiver) = mpsc::channel::<T>();
+    thread::spawn(move || {
+        while let Ok(event) = receiver.recv() {
+            on_event(event);
+        }
+    });
+
+    let task = POOL.spawn_with_handle(async move {
+        while let Some(event) = stream.next().await {
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    })?;
+
+    struct HolderImpl {
+        _task: RemoteHandle<()>,
+    }
+
+    impl Holder for HolderImpl {}
+
+    Ok(Box::new(HolderImpl { _task: task }))
+}
+
+// None of the linux backends understand `<local>`, wildcards differ only in the prefix before the domain
+fn translate_excludes(excludes: &[ProxyExclude], wildcard_prefix: &str) -> Vec<String> {
+    excludes
+        .iter()
+        .flat_map(|exclude| match exclude {
+            ProxyExclude::Local => vec!["localhost".to_owned(), "127.0.0.0/8".to_owned(), "::1".to_owned()],
+            ProxyExclude::Wildcard(domain) => vec![format!("{wildcard_prefix}{domain}")],
+            exclude => vec![exclude.to_string()],
+        })
+        .collect()
+}
+
+fn snapshot_path() -> Result<PathBuf, Box<dyn Error>> {
+    let state_dir = match std::env::var_os("XDG_STATE_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home::home_dir().ok_or("home directory not found")?.join(".local/state"),
+    };
+
+    Ok(state_dir.join("clash-multiplatform-compat").join("system-proxy.snapshot"))
+}
+
+// Snapshot file holds a `[<backend>]` line followed by backend specific settings for every backend we changed
+fn parse_snapshot(content: &str) -> Vec<(String, String)> {
+    let mut snapshots: Vec<(String, String)> = Vec::new();
+    for line in content.lines() {
+        match line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            Some(name) => snapshots.push((name.to_owned(), String::new())),
+            None => {
+                if let Some((_, settings)) = snapshots.last_mut() {
+                    settings.push_str(line);
+                    settings.push('\n');
+                }
+            }
+        }
+    }
+
+    snapshots
+}
+
+fn format_snapshot(snapshots: &[(String, String)]) -> String {
+    snapshots
+        .iter()
+        .map(|(name, settings)| match settings.trim_end_matches('\n') {
+            "" => format!("[{name}]\n"),
+            settings => format!("[{name}]\n{settings}\n"),
+        })
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(parse_snapshot(&content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn save_snapshot(path: &Path, snapshots: &[(String, String)]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    write_file_atomically(path, format_snapshot(snapshots).as_bytes(), None)
+}
+
+pub fn is_system_proxy_supported() -> bool {
+    primary_backend().is_some()
+}
+
+pub fn set_system_proxy(enabled: bool, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let backends = current_backends();
+    if backends.is_empty() {
+        return Err("unsupported".into());
+    }
+
+    // NetworkManager next to a desktop is best effort, there may be no connection to apply to
+    let optional = |backend: &Backend| *backend == Backend::NetworkManager && backends.len() > 1;
+
+    let path = snapshot_path()?;
+    let mut snapshots = read_snapshot(&path)?;
+
+    if enabled {
+        let targets: Vec<Backend> = backends.iter().copied().filter(|backend| backend.supports(config)).collect();
+        if targets.is_empty() {
+            return Err("NetworkManager only supports auto config proxies".into());
+        }
+
+        // Keep the oldest snapshot, settings already overridden by us are not worth restoring
+        let mut applied = Vec::new();
+        let mut saved = false;
+        for backend in targets {
+            if !snapshots.iter().any(|(name, _)| name == backend.name()) {
+                match backend.snapshot() {
+                    Ok(settings) => snapshots.push((backend.name().to_owned(), settings)),
+                    Err(_) if optional(&backend) => continue,
+                    Err(err) => return Err(err),
+                }
+
+                saved = true;
+            }
+
+            applied.push(backend);
+        }
+
+        if saved {
+            save_snapshot(&path, &snapshots)?;
+        }
+
+        for backend in applied {
+            match backend.set(true, config) {
+                Err(err) if !optional(&backend) => return Err(err),
+                _ => {}
+            }
+        }
+
+        return Ok(());
+    }
+
+    for backend in &backends {
+        let result = match snapshots.iter().find(|(name, _)| name == backend.name()) {
+            Some((_, settings)) => backend.restore(settings),
+
+            // Connection profiles are only changed when a pac script is applied, nothing of ours to clear otherwise
+            None if *backend == Backend::NetworkManager => Ok(()),
+            None => backend.set(false, config),
+        };
+
+        match result {
+            Err(err) if !optional(backend) => return Err(err),
+            _ => {}
+        }
+    }
+
+    match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn get_system_proxy() -> Result<SystemProxySettings, Box<dyn Error>> {
+    primary_backend().ok_or("unsupported")?.get()
+}
+
+fn is_config_applied(config: &SystemProxyConfig, settings: &SystemProxySettings) -> bool {
+    match &config.auto_config_url {
+        Some(url) => settings.mode == ProxyMode::Auto && settings.auto_config_url.as_ref() == Some(url),
+        None => {
+            settings.mode == ProxyMode::Manual
+                && settings.http == config.http
+                && settings.https == config.https
+                && settings.ftp == config.ftp
+                && settings.socks == config.socks
+        }
+    }
+}
+
+struct ChangeGuard<L: Listener> {
+    listener: L,
+    enforce: Option<SystemProxyConfig>,
+    last: Mutex<Option<SystemProxySettings>>,
+}
+
+impl<L: Listener> ChangeGuard<L> {
+    fn on_changed(&self) {
+        // Every backend notifies on its own thread, handle one change at a time
+        let mut last = self.last.lock().unwrap();
+
+        let backends = current_backends();
+
+        if let Some(Ok(settings)) = backends.first().map(Backend::get) {
+            if last.as_ref() != Some(&settings) {
+                *last = Some(settings.clone());
+
+                self.listener.on_changed(&settings);
+            }
+        }
+
+        if let Some(config) = &self.enforce {
+            // Backend that can not be read would fail to apply as well, leave it alone
+            let applied = backends.iter().filter(|backend| backend.supports(config)).all(|backend| {
+                backend
+                    .get()
+                    .map(|settings| is_config_applied(config, &settings))
+                    .unwrap_or(true)
+            });
+            if !applied {
+                let _ = set_system_proxy(true, config);
+            }
+        }
+    }
+}
+
+pub fn add_proxy_changed_listener(
+    enforce: Option<SystemProxyConfig>,
+    listener: impl Listener + Send + Sync + 'static,
+) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let backends = current_backends();
+    if backends.is_empty() {
+        return Err("unsupported".into());
+    }
+
+    let guard = Arc::new(ChangeGuard {
+        listener,
+        enforce,
+        last: Mutex::new(get_system_proxy().ok()),
+    });
+
+    let holders = backends
+        .iter()
+        .map(|backend| {
+            let guard = guard.clone();
+
+            backend.watch(move || guard.on_changed())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    struct HolderImpl {
+        _holders: Vec<Box<dyn Holder>>,
+    }
+
+    impl Holder for HolderImpl {}
+
+    Ok(Box::new(HolderImpl { _holders: holders }))
+}
+
+pub fn set_link_dns(enabled: bool, index: u32, servers: &[std::net::IpAddr]) -> Result<(), Box<dyn Error>> {
+    resolved::set_link_dns(enabled, index, servers)
+}
+
+pub fn set_proxy_environment(enabled: bool, app_id: &str, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    environment::set_proxy_environment(enabled, app_id, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::network::SystemProxyConfig,
+        linux::network::{detect_desktop, format_snapshot, parse_snapshot, Backend},
+    };
+
+    #[test]
+    pub fn test_snapshot() {
+        let snapshots = vec![
+            ("gnome".to_owned(), "/system/proxy/mode 6e6f6e65000073\n".to_owned()),
+            ("kde".to_owned(), "ProxyType=0\nNoProxyFor=localhost".to_owned()),
+            ("networkmanager".to_owned(), String::new()),
+        ];
+
+        let content = format_snapshot(&snapshots);
+        assert_eq!(
+            content,
+            "[gnome]\n/system/proxy/mode 6e6f6e65000073\n[kde]\nProxyType=0\nNoProxyFor=localhost\n[networkmanager]\n"
+        );
+
+        let parsed = parse_snapshot(&content);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(
+            parsed[1],
+            ("kde".to_owned(), "ProxyType=0\nNoProxyFor=localhost\n".to_owned())
+        );
+        assert_eq!(format_snapshot(&parsed), content);
+    }
+
+    #[test]
+    pub fn test_backend_supports() {
+        let manual = SystemProxyConfig {
+            http: None,
+            https: None,
+            ftp: None,
+            socks: None,
+            auto_config_url: None,
+            excludes: vec![],
+        };
+        let auto = SystemProxyConfig {
+            http: None,
+            https: None,
+            ftp: None,
+            socks: None,
+            auto_config_url: Some("http://127.0.0.1:7890/proxy.pac".to_owned()),
+            excludes: vec![],
+        };
+
+        assert!(Backend::Gnome.supports(&manual));
+        assert!(!Backend::NetworkManager.supports(&manual));
+        assert!(Backend::NetworkManager.supports(&auto));
+    }
+
+    #[test]
+    pub fn test_detect_desktop() {
+        assert_eq!(detect_desktop(Some("ubuntu:GNOME"), Some("ubuntu")), Some(Backend::Gnome));
+        assert_eq!(detect_desktop(Some("KDE"), Some("plasmawayland")), Some(Backend::Kde));
+        assert_eq!(detect_desktop(Some("XFCE"), Some("gnome")), None);
+        assert_eq!(detect_desktop(None, Some("plasmax11")), Some(Backend::Kde));
+        assert_eq!(detect_desktop(Some(""), Some("gnome")), Some(Backend::Gnome));
+        assert_eq!(detect_desktop(None, None), None);
+    }
+}
@@ -0,0 +1,484 @@
+use std::{collections::HashMap, error::Error};
+
+use futures::{
+    executor::block_on,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use zbus::{
+    fdo::DBusProxy,
+    names::BusName,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    CacheProperties, Connection, MatchRule, MessageStream, MessageType,
+};
+
+use crate::{
+    common::network::{Holder, ProxyMode, SystemProxyConfig, SystemProxySettings},
+    linux::dbus::network_manager::{ActiveProxy, ConnectionProxy, DeviceProxy, NetworkManagerProxy},
+};
+
+const SERVICE: &str = "org.freedesktop.NetworkManager";
+
+const PROXY_METHOD_NONE: i32 = 0;
+const PROXY_METHOD_AUTO: i32 = 1;
+
+// Keep profiles on disk untouched, in memory changes are dropped when NetworkManager restarts
+const UPDATE2_FLAG_IN_MEMORY: u32 = 0x2;
+
+type Settings = HashMap<String, HashMap<String, OwnedValue>>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ProxySetting {
+    method: i32,
+    pac_url: String,
+}
+
+struct ActiveConnection {
+    settings: OwnedObjectPath,
+    device: OwnedObjectPath,
+    specific_object: OwnedObjectPath,
+}
+
+fn proxy_setting(settings: &Settings) -> ProxySetting {
+    let value = |key: &str| settings.get("proxy").and_then(|proxy| proxy.get(key));
+
+    ProxySetting {
+        method: value("method")
+            .and_then(|v| v.downcast_ref::<i32>())
+            .copied()
+            .unwrap_or(PROXY_METHOD_NONE),
+        pac_url: value("pac-url")
+            .and_then(|v| v.downcast_ref::<str>())
+            .unwrap_or_default()
+            .to_owned(),
+    }
+}
+
+fn parse_settings(setting: &ProxySetting) -> SystemProxySettings {
+    match setting.method {
+        PROXY_METHOD_AUTO => SystemProxySettings {
+            mode: ProxyMode::Auto,
+            auto_config_url: Some(setting.pac_url.clone()).filter(|url| !url.is_empty()),
+            ..Default::default()
+        },
+        _ => SystemProxySettings::default(),
+    }
+}
+
+fn config_setting(enabled: bool, config: &SystemProxyConfig) -> Result<ProxySetting, Box<dyn Error>> {
+    if !enabled {
+        return Ok(ProxySetting {
+            method: PROXY_METHOD_NONE,
+            pac_url: String::new(),
+        });
+    }
+
+    // Connection profiles can only point to a pac script, there is no field for manual proxies
+    match &config.auto_config_url {
+        Some(url) => Ok(ProxySetting {
+            method: PROXY_METHOD_AUTO,
+            pac_url: url.clone(),
+        }),
+        None => Err("NetworkManager only supports auto config proxies".into()),
+    }
+}
+
+async fn primary_connection(conn: &Connection) -> Result<ActiveConnection, Box<dyn Error>> {
+    let path = NetworkManagerProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?
+        .primary_connection()
+        .await?;
+    if path.as_str() == "/" {
+        return Err("no primary connection".into());
+    }
+
+    let active = ActiveProxy::builder(conn)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let device = match active.devices().await?.into_iter().next() {
+        Some(device) => device,
+        None => ObjectPath::from_static_str_unchecked("/").into(),
+    };
+
+    Ok(ActiveConnection {
+        settings: active.connection().await?,
+        device,
+        specific_object: active.specific_object().await?,
+    })
+}
+
+async fn settings_connection<'a>(conn: &Connection, path: &'a ObjectPath<'a>) -> Result<ConnectionProxy<'a>, Box<dyn Error>> {
+    Ok(ConnectionProxy::builder(conn)
+        .path(path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?)
+}
+
+async fn read_setting(conn: &Connection, path: &ObjectPath<'_>) -> Result<ProxySetting, Box<dyn Error>> {
+    Ok(proxy_setting(&settings_connection(conn, path).await?.get_settings().await?))
+}
+
+async fn write_setting(conn: &Connection, path: &ObjectPath<'_>, setting: &ProxySetting) -> Result<(), Box<dyn Error>> {
+    let connection = settings_connection(conn, path).await?;
+
+    // Update2 replaces the whole profile, every other section is sent back as it was
+    let mut settings: HashMap<String, HashMap<String, Value>> = connection
+        .get_settings()
+        .await?
+        .into_iter()
+        .map(|(name, section)| (name, section.into_iter().map(|(k, v)| (k, Value::from(v))).collect()))
+        .collect();
+
+    let proxy = settings.entry("proxy".to_owned()).or_default();
+    proxy.insert("method".to_owned(), Value::from(setting.method));
+    proxy.insert("pac-url".to_owned(), Value::from(setting.pac_url.clone()));
+
+    let settings = settings
+        .iter()
+        .map(|(name, section)| (name.as_str(), section.iter().map(|(k, v)| (k.as_str(), v.clone())).collect()))
+        .collect();
+
+    connection.update2(settings, UPDATE2_FLAG_IN_MEMORY, HashMap::new()).await?;
+
+    Ok(())
+}
+
+async fn reapply(conn: &Connection, device: &ObjectPath<'_>) -> Result<(), Box<dyn Error>> {
+    if device.as_str() == "/" {
+        return Err("no device".into());
+    }
+
+    // Empty settings reapply the profile as it is now, version 0 skips the version check
+    DeviceProxy::builder(conn)
+        .path(device)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?
+        .reapply(HashMap::new(), 0, 0)
+        .await?;
+
+    Ok(())
+}
+
+async fn apply_setting(conn: &Connection, active: &ActiveConnection, setting: &ProxySetting) -> Result<(), Box<dyn Error>> {
+    // Applying may briefly interrupt the connection, skip it when nothing changes
+    if read_setting(conn, &active.settings).await? == *setting {
+        return Ok(());
+    }
+
+    write_setting(conn, &active.settings, setting).await?;
+
+    // Proxy settings are only picked up by the device when reapplied, activating the connection again is the
+    // fallback for devices that refuse to reapply and drops connectivity for a moment
+    if reapply(conn, &active.device).await.is_ok() {
+        return Ok(());
+    }
+
+    NetworkManagerProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?
+        .activate_connection(&active.settings, &active.device, &active.specific_object)
+        .await?;
+
+    Ok(())
+}
+
+fn format_snapshot(path: &ObjectPath<'_>, setting: &ProxySetting) -> String {
+    format!(
+        "connection={}\nmethod={}\npac-url={}\n",
+        path.as_str(),
+        setting.method,
+        setting.pac_url
+    )
+}
+
+fn parse_snapshot(snapshot: &str) -> Result<(OwnedObjectPath, ProxySetting), Box<dyn Error>> {
+    let values: HashMap<&str, &str> = snapshot.lines().filter_map(|line| line.split_once('=')).collect();
+    let value = |name: &str| values.get(name).copied().ok_or("invalid snapshot");
+
+    Ok((
+        OwnedObjectPath::try_from(value("connection")?)?,
+        ProxySetting {
+            method: value("method")?.parse()?,
+            pac_url: value("pac-url")?.to_owned(),
+        },
+    ))
+}
+
+async fn restore_setting(conn: &Connection, snapshot: &str) -> Result<(), Box<dyn Error>> {
+    let (path, setting) = parse_snapshot(snapshot)?;
+
+    match primary_connection(conn).await {
+        Ok(active) if active.settings == path => apply_setting(conn, &active, &setting).await,
+
+        // Inactive profile picks up the restored setting on its next activation
+        _ => write_setting(conn, &path, &setting).await,
+    }
+}
+
+pub fn is_available() -> bool {
+    block_on(async {
+        let conn = Connection::system().await?;
+        let name = BusName::try_from(SERVICE)?;
+
+        Ok(DBusProxy::new(&conn).await?.name_has_owner(name).await?) as Result<bool, Box<dyn Error>>
+    })
+    .unwrap_or(false)
+}
+
+pub fn get_system_proxy() -> Result<SystemProxySettings, Box<dyn Error>> {
+    block_on(async {
+        let conn = Connection::system().await?;
+        let active = primary_connection(&conn).await?;
+
+        Ok(parse_settings(&read_setting(&conn, &active.settings).await?))
+    })
+}
+
+pub fn set_system_proxy(enabled: bool, config: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let setting = config_setting(enabled, config)?;
+
+    block_on(async {
+        let conn = Connection::system().await?;
+
+        apply_setting(&conn, &primary_connection(&conn).await?, &setting).await
+    })
+}
+
+pub fn snapshot_system_proxy() -> Result<String, Box<dyn Error>> {
+    block_on(async {
+        let conn = Connection::system().await?;
+        let active = primary_connection(&conn).await?;
+
+        Ok(format_snapshot(
+            &active.settings,
+            &read_setting(&conn, &active.settings).await?,
+        ))
+    })
+}
+
+pub fn restore_system_proxy(snapshot: &str) -> Result<(), Box<dyn Error>> {
+    block_on(async { restore_setting(&Connection::system().await?, snapshot).await })
+}
+
+pub fn watch_system_proxy(on_changed: impl Fn() + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let changes: BoxStream<()> = block_on(async {
+        let conn = Connection::system().await?;
+
+        // Either the primary connection profile is edited or another connection becomes primary
+        let updated = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(SERVICE)?
+            .interface("org.freedesktop.NetworkManager.Settings.Connection")?
+            .member("Updated")?
+            .build();
+        let primary_changed = NetworkManagerProxy::new(&conn)
+            .await?
+            .receive_primary_connection_changed()
+            .await;
+
+        let updated = MessageStream::for_match_rule(updated, &conn, None).await?.map(|_| ());
+
+        Ok(stream::select(updated, primary_changed.map(|_| ())).boxed()) as Result<_, Box<dyn Error>>
+    })?;
+
+    super::watch_stream(changes, move |_| on_changed())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        error::Error,
+        os::unix::net::UnixStream,
+        sync::{
+            atomic::{AtomicBool, Ordering::Relaxed},
+            Arc, Mutex,
+        },
+    };
+
+    use futures::executor::block_on;
+    use zbus::{
+        dbus_interface,
+        zvariant::{OwnedObjectPath, OwnedValue, Value},
+        ConnectionBuilder, Guid,
+    };
+
+    use crate::{
+        common::network::{ProxyMode, SystemProxyConfig},
+        linux::network::networkmanager::{
+            apply_setting, config_setting, format_snapshot, parse_settings, primary_connection, read_setting, restore_setting,
+            Settings,
+        },
+    };
+
+    const ACTIVE_PATH: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings/1";
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/2";
+
+    struct MockManager(Arc<Mutex<Vec<String>>>);
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager")]
+    impl MockManager {
+        fn activate_connection(
+            &self,
+            connection: OwnedObjectPath,
+            device: OwnedObjectPath,
+            specific_object: OwnedObjectPath,
+        ) -> OwnedObjectPath {
+            self.0.lock().unwrap().push(format!(
+                "activate {} {} {}",
+                connection.as_str(),
+                device.as_str(),
+                specific_object.as_str()
+            ));
+
+            OwnedObjectPath::try_from(ACTIVE_PATH).unwrap()
+        }
+
+        #[dbus_interface(property)]
+        fn primary_connection(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(ACTIVE_PATH).unwrap()
+        }
+    }
+
+    struct MockActive;
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+    impl MockActive {
+        #[dbus_interface(property)]
+        fn connection(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(SETTINGS_PATH).unwrap()
+        }
+
+        #[dbus_interface(property)]
+        fn devices(&self) -> Vec<OwnedObjectPath> {
+            vec![OwnedObjectPath::try_from(DEVICE_PATH).unwrap()]
+        }
+
+        #[dbus_interface(property)]
+        fn specific_object(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from("/").unwrap()
+        }
+    }
+
+    struct MockDevice(Arc<Mutex<Vec<String>>>, Arc<AtomicBool>);
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Device")]
+    impl MockDevice {
+        fn reapply(&self, settings: Settings, version_id: u64, flags: u32) -> zbus::fdo::Result<()> {
+            if !self.1.load(Relaxed) {
+                return Err(zbus::fdo::Error::NotSupported("reapply".to_owned()));
+            }
+
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("reapply {} {version_id} {flags}", settings.len()));
+
+            Ok(())
+        }
+    }
+
+    struct MockConnection(Arc<Mutex<Settings>>, Arc<Mutex<Vec<String>>>);
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+    impl MockConnection {
+        fn get_settings(&self) -> Settings {
+            self.0.lock().unwrap().clone()
+        }
+
+        fn update2(&self, settings: Settings, flags: u32, _args: HashMap<String, OwnedValue>) -> HashMap<String, OwnedValue> {
+            self.1.lock().unwrap().push(format!("update {flags}"));
+
+            *self.0.lock().unwrap() = settings;
+
+            HashMap::new()
+        }
+    }
+
+    #[test]
+    pub fn test_connection_proxy() -> Result<(), Box<dyn Error>> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let reapply = Arc::new(AtomicBool::new(true));
+        let settings = Arc::new(Mutex::new(Settings::from([(
+            "connection".to_owned(),
+            HashMap::from([("id".to_owned(), OwnedValue::from(Value::from("Wired")))]),
+        )])));
+
+        block_on(async {
+            let (server, client) = UnixStream::pair()?;
+            let guid = Guid::generate();
+
+            let server = ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at("/org/freedesktop/NetworkManager", MockManager(calls.clone()))?
+                .serve_at(ACTIVE_PATH, MockActive)?
+                .serve_at(DEVICE_PATH, MockDevice(calls.clone(), reapply.clone()))?
+                .serve_at(SETTINGS_PATH, MockConnection(settings.clone(), calls.clone()))?
+                .build();
+            let client = ConnectionBuilder::unix_stream(client).p2p().build();
+            let (_server, client) = futures::future::try_join(server, client).await?;
+
+            let active = primary_connection(&client).await?;
+            let snapshot = format_snapshot(&active.settings, &read_setting(&client, &active.settings).await?);
+
+            let config = SystemProxyConfig {
+                http: None,
+                https: None,
+                ftp: None,
+                socks: None,
+                auto_config_url: Some("http://127.0.0.1:7890/proxy.pac".to_owned()),
+                excludes: vec![],
+            };
+            apply_setting(&client, &active, &config_setting(true, &config)?).await?;
+            apply_setting(&client, &active, &config_setting(true, &config)?).await?;
+
+            let applied = parse_settings(&read_setting(&client, &active.settings).await?);
+            assert_eq!(applied.mode, ProxyMode::Auto);
+            assert_eq!(applied.auto_config_url, config.auto_config_url);
+
+            // Devices refusing to reapply fall back to activating the connection again
+            reapply.store(false, Relaxed);
+
+            restore_setting(&client, &snapshot).await?;
+
+            let restored = parse_settings(&read_setting(&client, &active.settings).await?);
+            assert_eq!(restored.mode, ProxyMode::None);
+
+            Ok(()) as Result<(), Box<dyn Error>>
+        })?;
+
+        let settings = settings.lock().unwrap();
+        assert!(settings.contains_key("connection"));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "update 2".to_owned(),
+                "reapply 0 0 0".to_owned(),
+                "update 2".to_owned(),
+                format!("activate {SETTINGS_PATH} {DEVICE_PATH} /"),
+            ]
+        );
+
+        let manual = SystemProxyConfig {
+            http: None,
+            https: None,
+            ftp: None,
+            socks: None,
+            auto_config_url: None,
+            excludes: vec![],
+        };
+        assert!(config_setting(true, &manual).is_err());
+        assert!(config_setting(false, &manual).is_ok());
+
+        Ok(())
+    }
+}
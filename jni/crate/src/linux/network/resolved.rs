@@ -0,0 +1,130 @@
+use std::{error::Error, net::IpAddr};
+
+use futures::executor::block_on;
+use libc::{AF_INET, AF_INET6};
+use zbus::Connection;
+
+use crate::linux::dbus::resolve1_manager::ManagerProxy;
+
+// Routing-only root domain, makes the link preferred for all lookups
+const ROUTING_DOMAIN: (&str, bool) = (".", true);
+
+fn to_dns_address(address: &IpAddr) -> (i32, Vec<u8>) {
+    match address {
+        IpAddr::V4(address) => (AF_INET, address.octets().to_vec()),
+        IpAddr::V6(address) => (AF_INET6, address.octets().to_vec()),
+    }
+}
+
+async fn apply_link_dns(conn: &Connection, index: i32, servers: &[IpAddr]) -> Result<(), Box<dyn Error>> {
+    let manager = ManagerProxy::new(conn).await?;
+
+    let addresses = servers.iter().map(to_dns_address).collect::<Vec<_>>();
+    let addresses = addresses
+        .iter()
+        .map(|(family, address)| (*family, address.as_slice()))
+        .collect::<Vec<_>>();
+
+    manager.set_link_dns(index, &addresses.iter().collect::<Vec<_>>()).await?;
+    manager.set_link_domains(index, &[&ROUTING_DOMAIN]).await?;
+    manager.set_link_default_route(index, true).await?;
+
+    Ok(())
+}
+
+async fn revert_link_dns(conn: &Connection, index: i32) -> Result<(), Box<dyn Error>> {
+    ManagerProxy::new(conn).await?.revert_link(index).await?;
+
+    Ok(())
+}
+
+pub fn set_link_dns(enabled: bool, index: u32, servers: &[IpAddr]) -> Result<(), Box<dyn Error>> {
+    let index = i32::try_from(index)?;
+    if enabled && servers.is_empty() {
+        return Err("no dns servers".into());
+    }
+
+    block_on(async {
+        let conn = Connection::system().await?;
+
+        if enabled {
+            apply_link_dns(&conn, index, servers).await
+        } else {
+            revert_link_dns(&conn, index).await
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        net::IpAddr,
+        os::unix::net::UnixStream,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::executor::block_on;
+    use zbus::{dbus_interface, ConnectionBuilder, Guid};
+
+    use crate::linux::network::resolved::{apply_link_dns, revert_link_dns};
+
+    struct MockManager(Arc<Mutex<Vec<String>>>);
+
+    #[dbus_interface(name = "org.freedesktop.resolve1.Manager")]
+    impl MockManager {
+        #[dbus_interface(name = "SetLinkDNS")]
+        fn set_link_dns(&self, ifindex: i32, addresses: Vec<(i32, Vec<u8>)>) {
+            self.0.lock().unwrap().push(format!("dns {ifindex} {addresses:?}"));
+        }
+
+        fn set_link_domains(&self, ifindex: i32, domains: Vec<(String, bool)>) {
+            self.0.lock().unwrap().push(format!("domains {ifindex} {domains:?}"));
+        }
+
+        fn set_link_default_route(&self, ifindex: i32, enable: bool) {
+            self.0.lock().unwrap().push(format!("default-route {ifindex} {enable}"));
+        }
+
+        fn revert_link(&self, ifindex: i32) {
+            self.0.lock().unwrap().push(format!("revert {ifindex}"));
+        }
+    }
+
+    #[test]
+    pub fn test_link_dns() -> Result<(), Box<dyn Error>> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        block_on(async {
+            let (server, client) = UnixStream::pair()?;
+            let guid = Guid::generate();
+
+            let server = ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at("/org/freedesktop/resolve1", MockManager(calls.clone()))?
+                .build();
+            let client = ConnectionBuilder::unix_stream(client).p2p().build();
+            let (_server, client) = futures::future::try_join(server, client).await?;
+
+            let servers = ["198.18.0.2".parse::<IpAddr>()?, "fdfe:dcba:9876::2".parse::<IpAddr>()?];
+
+            apply_link_dns(&client, 42, &servers).await?;
+            revert_link_dns(&client, 42).await?;
+
+            Ok(()) as Result<(), Box<dyn Error>>
+        })?;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "dns 42 [(2, [198, 18, 0, 2]), (10, [253, 254, 220, 186, 152, 118, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2])]",
+                "domains 42 [(\".\", true)]",
+                "default-route 42 true",
+                "revert 42",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashSet, error::Error, io::ErrorKind};
+
+use crate::common::network::{PortOwner, Protocol};
+
+const TCP_LISTEN: u8 = 0x0a;
+
+fn parse_socket_inodes(content: &str, port: u16, listen_only: bool) -> HashSet<u64> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() < 10 {
+                return None;
+            }
+
+            let (_, local_port) = fields[1].rsplit_once(':')?;
+            let state = u8::from_str_radix(fields[3], 16).ok()?;
+            let inode = fields[9].parse::<u64>().ok()?;
+
+            if u16::from_str_radix(local_port, 16).ok()? != port || inode == 0 || (listen_only && state != TCP_LISTEN) {
+                return None;
+            }
+
+            Some(inode)
+        })
+        .collect()
+}
+
+fn read_proc_net(name: &str) -> Result<String, Box<dyn Error>> {
+    match std::fs::read_to_string(format!("/proc/net/{name}")) {
+        // IPv6 may be disabled
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(String::new()),
+        result => Ok(result?),
+    }
+}
+
+fn process_owns_socket(pid: u32, inodes: &HashSet<u64>) -> bool {
+    let fds = match std::fs::read_dir(format!("/proc/{pid}/fd")) {
+        Ok(fds) => fds,
+        Err(_) => return false,
+    };
+
+    fds.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+        .filter_map(|target| {
+            let target = target.to_str()?;
+
+            target.strip_prefix("socket:[")?.strip_suffix(']')?.parse::<u64>().ok()
+        })
+        .any(|inode| inodes.contains(&inode))
+}
+
+fn read_port_owner(pid: u32) -> PortOwner {
+    let executable = std::fs::read_link(format!("/proc/{pid}/exe"))
+        .ok()
+        .and_then(|path| path.to_str().map(|path| path.to_owned()));
+
+    let command_line = std::fs::read(format!("/proc/{pid}/cmdline"))
+        .unwrap_or_default()
+        .split(|c| *c == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+
+    PortOwner {
+        pid,
+        executable,
+        command_line,
+    }
+}
+
+pub fn find_port_owners(protocol: Protocol, port: u16) -> Result<Vec<PortOwner>, Box<dyn Error>> {
+    let (tables, listen_only) = match protocol {
+        Protocol::Tcp => (["tcp", "tcp6"], true),
+        Protocol::Udp => (["udp", "udp6"], false),
+    };
+
+    let mut inodes = HashSet::new();
+    for table in tables {
+        inodes.extend(parse_socket_inodes(&read_proc_net(table)?, port, listen_only));
+    }
+
+    if inodes.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Sockets of processes owned by other users are invisible without privileges
+    let mut owners = std::fs::read_dir("/proc")?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| process_owns_socket(*pid, &inodes))
+        .map(read_port_owner)
+        .collect::<Vec<_>>();
+
+    owners.sort_by_key(|owner| owner.pid);
+
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        net::{TcpListener, TcpStream, UdpSocket},
+    };
+
+    use crate::{
+        common::network::Protocol,
+        linux::port::{find_port_owners, parse_socket_inodes},
+    };
+
+    #[test]
+    pub fn test_parse_socket_inodes() {
+        let content = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1F90 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 4343 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 4444 1 0000000000000000 100 0 0 10 0
+";
+
+        assert_eq!(parse_socket_inodes(content, 8080, true), [4242].into());
+        assert_eq!(parse_socket_inodes(content, 8080, false), [4242, 4343].into());
+        assert!(parse_socket_inodes(content, 443, false).is_empty());
+    }
+
+    #[test]
+    pub fn test_find_port_owners() -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let _client = TcpStream::connect(listener.local_addr()?)?;
+
+        let owners = find_port_owners(Protocol::Tcp, port)?;
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].pid, std::process::id());
+        assert_eq!(owners[0].executable.as_deref(), std::env::current_exe()?.to_str());
+        assert!(!owners[0].command_line.is_empty());
+
+        let socket = UdpSocket::bind("[::1]:0")?;
+        let owners = find_port_owners(Protocol::Udp, socket.local_addr()?.port())?;
+        assert!(owners.iter().any(|owner| owner.pid == std::process::id()));
+
+        Ok(())
+    }
+}
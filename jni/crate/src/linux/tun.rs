@@ -0,0 +1,273 @@
+use std::{
+    error::Error,
+    ffi::{CStr, CString},
+    mem::{size_of, zeroed},
+    net::IpAddr,
+};
+
+use cstr::cstr;
+use libc::{
+    c_char, c_int, c_short, c_ulong, if_nametoindex, ifreq, ioctl, open, AF_INET, AF_INET6, AF_UNSPEC, IFA_ADDRESS, IFA_F_NODAD,
+    IFA_LOCAL, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TUN, IFF_UP, IFLA_MTU, IFNAMSIZ, NLM_F_CREATE, NLM_F_REPLACE, O_CLOEXEC, O_RDWR,
+    RTM_NEWADDR, RTM_NEWLINK,
+};
+
+use crate::{
+    common::{
+        file::FileDescriptor,
+        network::{InterfaceAddress, TunConfig},
+    },
+    linux::{
+        errno::syscall,
+        netlink::{IfAddrMsg, IfInfoMsg, Message, Netlink},
+    },
+    utils::scoped::Scoped,
+};
+
+// _IOW('T', 202, int) from linux/if_tun.h, libc does not export the tun ioctls
+const TUNSETIFF: c_ulong = ioctl_write::<c_int>(b'T', 202);
+
+const fn ioctl_write<T>(kind: u8, number: u8) -> c_ulong {
+    const IOC_WRITE: c_ulong = 1;
+
+    (IOC_WRITE << 30) | ((size_of::<T>() as c_ulong) << 16) | ((kind as c_ulong) << 8) | number as c_ulong
+}
+
+fn open_queue(name: &str, flags: c_int) -> Result<(Scoped<c_int>, String), Box<dyn Error>> {
+    let fd = Scoped::new_fd(unsafe { syscall(|| open(cstr!("/dev/net/tun").as_ptr(), O_RDWR | O_CLOEXEC))? });
+
+    let mut request: ifreq = unsafe { zeroed() };
+    for (dst, src) in request.ifr_name.iter_mut().zip(name.as_bytes()) {
+        *dst = *src as c_char;
+    }
+    request.ifr_ifru.ifru_flags = flags as c_short;
+
+    unsafe { syscall(|| ioctl(*fd, TUNSETIFF as _, &mut request))? };
+
+    let name = unsafe { CStr::from_ptr(request.ifr_name.as_ptr()) }.to_str()?.to_owned();
+
+    Ok((fd, name))
+}
+
+pub fn create_tun(config: &TunConfig) -> Result<(String, Vec<FileDescriptor>), Box<dyn Error>> {
+    if config.name.len() >= IFNAMSIZ {
+        return Err(format!("interface name too long: {}", config.name).into());
+    }
+
+    let addresses = config
+        .addresses
+        .iter()
+        .map(|address| address.parse::<InterfaceAddress>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut flags = IFF_TUN | IFF_NO_PI;
+    if config.queues > 1 {
+        flags |= IFF_MULTI_QUEUE;
+    }
+
+    let (fd, name) = open_queue(&config.name, flags)?;
+
+    let mut fds = vec![fd];
+    for _ in 1..config.queues {
+        fds.push(open_queue(&name, flags)?.0);
+    }
+
+    let index = unsafe { if_nametoindex(CString::new(name.as_str())?.as_ptr()) };
+    if index == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let mut netlink = Netlink::open()?;
+
+    let header = IfInfoMsg {
+        family: AF_UNSPEC as u8,
+        index: index as i32,
+        flags: IFF_UP as u32,
+        change: IFF_UP as u32,
+        ..Default::default()
+    };
+
+    let mut message = Message::new(RTM_NEWLINK, 0, &header);
+    if config.mtu > 0 {
+        message = message.attr(IFLA_MTU, &config.mtu.to_ne_bytes());
+    }
+
+    netlink.execute(message)?;
+
+    for InterfaceAddress { address, prefix_len } in addresses {
+        let (family, octets) = match address {
+            IpAddr::V4(address) => (AF_INET, address.octets().to_vec()),
+            IpAddr::V6(address) => (AF_INET6, address.octets().to_vec()),
+        };
+
+        let header = IfAddrMsg {
+            family: family as u8,
+            prefix_len,
+            flags: IFA_F_NODAD as u8,
+            scope: 0,
+            index,
+        };
+
+        netlink.execute(
+            Message::new(RTM_NEWADDR, NLM_F_CREATE | NLM_F_REPLACE, &header)
+                .attr(IFA_LOCAL, &octets)
+                .attr(IFA_ADDRESS, &octets),
+        )?;
+    }
+
+    Ok((name, fds.iter_mut().map(|fd| fd.swap(-1) as FileDescriptor).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        ffi::CStr,
+        mem::zeroed,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        process::{Command, Stdio},
+    };
+
+    use libc::{
+        c_char, close, freeifaddrs, getifaddrs, ifaddrs, ioctl, sockaddr_in, sockaddr_in6, socket, AF_INET, AF_INET6, IFF_UP,
+        SIOCGIFMTU, SOCK_CLOEXEC, SOCK_DGRAM,
+    };
+
+    use crate::{
+        common::network::TunConfig,
+        linux::tun::{create_tun, TUNSETIFF},
+    };
+
+    const NAMESPACE_ENV: &str = "COMPAT_TEST_IN_NAMESPACE";
+
+    fn list_addresses(name: &str) -> Vec<(IpAddr, bool)> {
+        let mut addresses = Vec::new();
+
+        unsafe {
+            let mut list: *mut ifaddrs = std::ptr::null_mut();
+            assert_eq!(getifaddrs(&mut list), 0);
+
+            let mut current = list;
+            while !current.is_null() {
+                let entry = &*current;
+                current = entry.ifa_next;
+
+                if entry.ifa_addr.is_null() || CStr::from_ptr(entry.ifa_name).to_str() != Ok(name) {
+                    continue;
+                }
+
+                let address = match (*entry.ifa_addr).sa_family as i32 {
+                    AF_INET => IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        (*entry.ifa_addr.cast::<sockaddr_in>()).sin_addr.s_addr,
+                    ))),
+                    AF_INET6 => IpAddr::V6(Ipv6Addr::from((*entry.ifa_addr.cast::<sockaddr_in6>()).sin6_addr.s6_addr)),
+                    _ => continue,
+                };
+
+                addresses.push((address, entry.ifa_flags & IFF_UP as u32 != 0));
+            }
+
+            freeifaddrs(list);
+        }
+
+        addresses
+    }
+
+    fn get_mtu(name: &str) -> i32 {
+        unsafe {
+            let fd = socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0);
+
+            let mut request: libc::ifreq = zeroed();
+            for (dst, src) in request.ifr_name.iter_mut().zip(name.as_bytes()) {
+                *dst = *src as c_char;
+            }
+
+            assert_eq!(ioctl(fd, SIOCGIFMTU, &mut request), 0);
+
+            close(fd);
+
+            request.ifr_ifru.ifru_mtu
+        }
+    }
+
+    #[test]
+    pub fn test_create_tun() -> Result<(), Box<dyn Error>> {
+        if std::env::var_os(NAMESPACE_ENV).is_none() {
+            let namespace_supported = Command::new("unshare")
+                .args(["--user", "--map-root-user", "--net", "true"])
+                .stderr(Stdio::null())
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+            if !namespace_supported {
+                println!("Unprivileged user namespace unavailable, skipped");
+
+                return Ok(());
+            }
+
+            let status = Command::new("unshare")
+                .args(["--user", "--map-root-user", "--net"])
+                .arg(std::env::current_exe()?)
+                .args(["--exact", "linux::tun::tests::test_create_tun", "--nocapture"])
+                .env(NAMESPACE_ENV, "1")
+                .status()?;
+            assert!(status.success());
+
+            return Ok(());
+        }
+
+        let (name, fds) = create_tun(&TunConfig {
+            name: "compat0".to_owned(),
+            mtu: 9000,
+            addresses: vec!["198.18.0.1/16".to_owned(), "fdfe:dcba:9876::1/126".to_owned()],
+            queues: 2,
+        })?;
+
+        assert_eq!(name, "compat0");
+        assert_eq!(fds.len(), 2);
+        assert_eq!(get_mtu(&name), 9000);
+
+        let mut addresses = list_addresses(&name)
+            .into_iter()
+            .filter(|(address, _)| !matches!(address, IpAddr::V6(address) if address.segments()[0] & 0xffc0 == 0xfe80))
+            .collect::<Vec<_>>();
+        addresses.sort();
+        assert_eq!(
+            addresses,
+            vec![("198.18.0.1".parse()?, true), ("fdfe:dcba:9876::1".parse()?, true),]
+        );
+
+        for fd in fds {
+            unsafe { close(fd as _) };
+        }
+
+        assert!(list_addresses(&name).is_empty());
+
+        let (name, fds) = create_tun(&TunConfig {
+            name: "".to_owned(),
+            mtu: 0,
+            addresses: vec![],
+            queues: 1,
+        })?;
+
+        assert!(name.starts_with("tun"));
+        assert_eq!(fds.len(), 1);
+
+        unsafe { close(fds[0] as _) };
+
+        assert!(create_tun(&TunConfig {
+            name: "compat1".to_owned(),
+            mtu: 0,
+            addresses: vec!["198.18.0.1/33".to_owned()],
+            queues: 1,
+        })
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_tunsetiff() {
+        assert_eq!(TUNSETIFF, 0x400454ca);
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::{CString, OsStr},
+    mem::size_of,
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    ptr::read_unaligned,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use libc::{
+    c_int, c_void, eventfd, inotify_add_watch, inotify_event, inotify_init1, poll, pollfd, read, write, EFD_CLOEXEC,
+    EFD_NONBLOCK, EINTR, IN_CLOEXEC, IN_CLOSE_WRITE, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_IGNORED, IN_MODIFY, IN_MOVED_FROM,
+    IN_MOVED_TO, IN_MOVE_SELF, IN_NONBLOCK, IN_Q_OVERFLOW, POLLIN,
+};
+
+use crate::{
+    common::watcher::{Holder, Listener, Watch, EVENT_CREATE, EVENT_DELETE, EVENT_MODIFY, EVENT_MOVE},
+    linux::errno::syscall,
+    utils::scoped::Scoped,
+};
+
+const COALESCE_WINDOW: Duration = Duration::from_millis(100);
+
+fn to_inotify_mask(events: u32) -> u32 {
+    let mut mask = 0;
+
+    if events & EVENT_CREATE != 0 {
+        mask |= IN_CREATE;
+    }
+    if events & EVENT_MODIFY != 0 {
+        mask |= IN_MODIFY | IN_CLOSE_WRITE;
+    }
+    if events & EVENT_DELETE != 0 {
+        mask |= IN_DELETE | IN_DELETE_SELF;
+    }
+    if events & EVENT_MOVE != 0 {
+        mask |= IN_MOVED_FROM | IN_MOVED_TO | IN_MOVE_SELF;
+    }
+
+    mask
+}
+
+fn from_inotify_mask(mask: u32) -> u32 {
+    let mut events = 0;
+
+    if mask & IN_CREATE != 0 {
+        events |= EVENT_CREATE;
+    }
+    if mask & (IN_MODIFY | IN_CLOSE_WRITE) != 0 {
+        events |= EVENT_MODIFY;
+    }
+    if mask & (IN_DELETE | IN_DELETE_SELF) != 0 {
+        events |= EVENT_DELETE;
+    }
+    if mask & (IN_MOVED_FROM | IN_MOVED_TO | IN_MOVE_SELF) != 0 {
+        events |= EVENT_MOVE;
+    }
+
+    events
+}
+
+struct Watching {
+    inotify_fd: Scoped<c_int>,
+    stop_fd: c_int,
+    paths: HashMap<c_int, (PathBuf, u32)>,
+    pending: HashMap<PathBuf, u32>,
+}
+
+impl Watching {
+    fn drain(&mut self) -> bool {
+        let mut buffer = [0u64; 512];
+
+        loop {
+            let length = unsafe { read(*self.inotify_fd, buffer.as_mut_ptr().cast(), size_of::<[u64; 512]>()) };
+            if length <= 0 {
+                return true;
+            }
+
+            let buffer: &[u8] = unsafe { std::slice::from_raw_parts(buffer.as_ptr().cast(), length as usize) };
+            let mut offset = 0;
+
+            while offset + size_of::<inotify_event>() <= buffer.len() {
+                let event: inotify_event = unsafe { read_unaligned(buffer[offset..].as_ptr().cast()) };
+                let name = &buffer[offset + size_of::<inotify_event>()..][..event.len as usize];
+                let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+
+                offset += size_of::<inotify_event>() + event.len as usize;
+
+                if event.mask & IN_Q_OVERFLOW != 0 {
+                    for (path, events) in self.paths.values() {
+                        *self.pending.entry(path.clone()).or_default() |= *events;
+                    }
+
+                    continue;
+                }
+
+                if event.mask & IN_IGNORED != 0 {
+                    self.paths.remove(&event.wd);
+
+                    continue;
+                }
+
+                if let Some((path, _)) = self.paths.get(&event.wd) {
+                    let path = if name.is_empty() {
+                        path.clone()
+                    } else {
+                        path.join(OsStr::from_bytes(name))
+                    };
+
+                    let events = from_inotify_mask(event.mask);
+                    if events != 0 {
+                        *self.pending.entry(path).or_default() |= events;
+                    }
+                }
+            }
+
+            if self.paths.is_empty() {
+                return false;
+            }
+        }
+    }
+
+    fn run(mut self, listener: impl Listener) {
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let timeout = match deadline {
+                // Round up, a truncated timeout wakes up early and spins until the deadline
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_micros().div_ceil(1000) as c_int,
+                None => -1,
+            };
+
+            let mut fds = [
+                pollfd {
+                    fd: *self.inotify_fd,
+                    events: POLLIN,
+                    revents: 0,
+                },
+                pollfd {
+                    fd: self.stop_fd,
+                    events: POLLIN,
+                    revents: 0,
+                },
+            ];
+
+            if let Err(err) = unsafe { syscall(|| poll(fds.as_mut_ptr(), fds.len() as _, timeout)) } {
+                if err.0 == EINTR {
+                    continue;
+                }
+
+                return;
+            }
+
+            if fds[1].revents != 0 {
+                return;
+            }
+
+            let alive = fds[0].revents == 0 || self.drain();
+
+            if deadline.is_none() && !self.pending.is_empty() {
+                deadline = Some(Instant::now() + COALESCE_WINDOW);
+            }
+
+            if !alive || deadline.map(|d| d <= Instant::now()).unwrap_or(false) {
+                for (path, events) in self.pending.drain() {
+                    listener.on_changed(path.as_os_str().as_bytes(), events);
+                }
+
+                deadline = None;
+            }
+
+            if !alive {
+                return;
+            }
+        }
+    }
+}
+
+struct HolderImpl {
+    stop_fd: Scoped<c_int>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Holder for HolderImpl {}
+
+impl Drop for HolderImpl {
+    fn drop(&mut self) {
+        let value: u64 = 1;
+
+        unsafe {
+            write(*self.stop_fd, (&value as *const u64).cast::<c_void>(), size_of::<u64>());
+        }
+
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != std::thread::current().id() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+pub fn add_watcher(watches: &[Watch], listener: impl Listener + Send + 'static) -> Result<Box<dyn Holder>, Box<dyn Error>> {
+    let inotify_fd = Scoped::new_fd(unsafe { syscall(|| inotify_init1(IN_NONBLOCK | IN_CLOEXEC))? });
+
+    let mut paths = HashMap::with_capacity(watches.len());
+    for watch in watches {
+        let path = CString::new(watch.path.as_str())?;
+        let wd = unsafe { syscall(|| inotify_add_watch(*inotify_fd, path.as_ptr(), to_inotify_mask(watch.events)))? };
+
+        paths.insert(wd, (PathBuf::from(&watch.path), watch.events));
+    }
+
+    let stop_fd = Scoped::new_fd(unsafe { syscall(|| eventfd(0, EFD_NONBLOCK | EFD_CLOEXEC))? });
+
+    let watching = Watching {
+        inotify_fd,
+        stop_fd: *stop_fd,
+        paths,
+        pending: HashMap::new(),
+    };
+
+    let thread = std::thread::Builder::new()
+        .name("compat-watcher".to_owned())
+        .spawn(move || watching.run(listener))?;
+
+    Ok(Box::new(HolderImpl {
+        stop_fd,
+        thread: Some(thread),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, ffi::OsStr, os::unix::ffi::OsStrExt, sync::mpsc, time::Duration};
+
+    use crate::{
+        common::watcher::{Listener, Watch, EVENT_CREATE, EVENT_DELETE, EVENT_MODIFY, EVENT_MOVE},
+        linux::watcher::add_watcher,
+    };
+
+    struct ListenerImpl(mpsc::Sender<(String, u32)>);
+
+    impl Listener for ListenerImpl {
+        fn on_changed(&self, path: &[u8], events: u32) {
+            self.0.send((String::from_utf8_lossy(path).into_owned(), events)).ok();
+        }
+    }
+
+    struct RawListenerImpl(mpsc::Sender<(Vec<u8>, u32)>);
+
+    impl Listener for RawListenerImpl {
+        fn on_changed(&self, path: &[u8], events: u32) {
+            self.0.send((path.to_vec(), events)).ok();
+        }
+    }
+
+    #[test]
+    pub fn test_watch_directory() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("compat-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let (tx, rx) = mpsc::channel();
+        let holder = add_watcher(
+            &[Watch {
+                path: dir.to_str().unwrap().to_owned(),
+                events: EVENT_CREATE | EVENT_MODIFY | EVENT_DELETE | EVENT_MOVE,
+            }],
+            ListenerImpl(tx),
+        )?;
+
+        std::fs::write(dir.join("config.yaml"), "mixed-port: 7890")?;
+        std::fs::write(dir.join("config.yaml"), "mixed-port: 7891")?;
+
+        let (path, events) = rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(path, dir.join("config.yaml").to_str().unwrap());
+        assert_eq!(events, EVENT_CREATE | EVENT_MODIFY);
+
+        std::fs::rename(dir.join("config.yaml"), dir.join("config.yaml.bak"))?;
+        std::fs::remove_file(dir.join("config.yaml.bak"))?;
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.recv_timeout(Duration::from_secs(1)) {
+            events.push(event);
+        }
+        events.sort();
+
+        assert_eq!(
+            events,
+            vec![
+                (dir.join("config.yaml").to_str().unwrap().to_owned(), EVENT_MOVE),
+                (
+                    dir.join("config.yaml.bak").to_str().unwrap().to_owned(),
+                    EVENT_DELETE | EVENT_MOVE
+                ),
+            ]
+        );
+
+        drop(holder);
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_watch_non_utf8_name() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("compat-watcher-raw-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let (tx, rx) = mpsc::channel();
+        let holder = add_watcher(
+            &[Watch {
+                path: dir.to_str().unwrap().to_owned(),
+                events: EVENT_CREATE,
+            }],
+            RawListenerImpl(tx),
+        )?;
+
+        let file = dir.join(OsStr::from_bytes(b"config-\xff.yaml"));
+        std::fs::write(&file, "mixed-port: 7890")?;
+
+        let (path, events) = rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(path, file.as_os_str().as_bytes());
+        assert_eq!(events, EVENT_CREATE);
+
+        drop(holder);
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,26 @@
+use std::{
+    error::Error,
+    net::{Ipv6Addr, SocketAddr},
+    ptr::null_mut,
+};
+
 use crate::{
-    common::network::SystemProxyConfig,
-    helper::{array::iterate_object_array, strings::java_string_to_string, throwable::rethrow_java_io_exception},
+    common::network::{
+        BindRequest, Holder, InterfaceAddress, NetworkInterface, OperState, PortOwner, ProxyEndpoint, ProxyExclude, ProxyMode,
+        Route, Rule, SystemProxyConfig, SystemProxySettings,
+    },
+    helper::{
+        array::{
+            collect_java_ints, iterate_object_array, new_java_byte_array, new_java_int_array, new_java_object_array,
+            new_java_string_array,
+        },
+        call::jcall,
+        lazy::{JRef, LazyJRef},
+        strings::{java_string_to_string, string_to_java_string},
+        throwable::rethrow_java_io_exception,
+    },
 };
-use jni_sys::{jboolean, jclass, jobjectArray, jstring, JNIEnv, JNI_FALSE, JNI_TRUE};
+use jni_sys::{jboolean, jclass, jint, jintArray, jlong, jmethodID, jobject, jobjectArray, jstring, JNIEnv, JNI_FALSE, JNI_TRUE};
 
 #[no_mangle]
 pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeIsSystemProxySupported(
@@ -22,22 +40,83 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeIsSyste
     return JNI_TRUE;
 }
 
+fn parse_proxy_endpoint(host: &str, port: jint) -> Result<Option<ProxyEndpoint>, Box<dyn Error>> {
+    let host = host.trim();
+    if host.is_empty() {
+        return Ok(None);
+    }
+
+    let host = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(ip) => ip.parse::<Ipv6Addr>()?.to_string(),
+        None if host.contains(':') => host.parse::<Ipv6Addr>()?.to_string(),
+        None if host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_') =>
+        {
+            host.to_owned()
+        }
+        None => return Err(format!("invalid proxy host: {host}").into()),
+    };
+
+    let port = u16::try_from(port).ok().filter(|port| *port != 0);
+
+    Ok(Some(ProxyEndpoint {
+        host,
+        port: port.ok_or("invalid proxy port")?,
+    }))
+}
+
+fn read_system_proxy_config(
+    env: *mut JNIEnv,
+    hosts: jobjectArray,
+    ports: jintArray,
+    auto_config_url: jstring,
+    excludes: jobjectArray,
+) -> Result<SystemProxyConfig, Box<dyn Error>> {
+    let auto_config_url = if auto_config_url.is_null() {
+        None
+    } else {
+        let url = java_string_to_string(env, auto_config_url);
+        if !url.contains("://") {
+            return Err(format!("invalid auto config url: {url}").into());
+        }
+
+        Some(url)
+    };
+
+    let mut endpoints = iterate_object_array(env, hosts)
+        .map(|s| java_string_to_string(env, s))
+        .zip(collect_java_ints(env, ports))
+        .map(|(host, port)| parse_proxy_endpoint(&host, port))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+
+    Ok(SystemProxyConfig {
+        http: endpoints.next().flatten(),
+        https: endpoints.next().flatten(),
+        ftp: endpoints.next().flatten(),
+        socks: endpoints.next().flatten(),
+        auto_config_url,
+        excludes: iterate_object_array(env, excludes)
+            .map(|s| java_string_to_string(env, s))
+            .map(|s| s.parse::<ProxyExclude>())
+            .collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeSetSystemProxy(
     env: *mut JNIEnv,
     _: jclass,
     enabled: jboolean,
-    address: jstring,
+    hosts: jobjectArray,
+    ports: jintArray,
+    auto_config_url: jstring,
     excludes: jobjectArray,
 ) {
     rethrow_java_io_exception(env, || {
         let enabled = enabled != JNI_FALSE;
-        let cfg = SystemProxyConfig {
-            address: java_string_to_string(env, address),
-            excludes: iterate_object_array(env, excludes)
-                .map(|s| java_string_to_string(env, s))
-                .collect::<Vec<_>>(),
-        };
+        let cfg = read_system_proxy_config(env, hosts, ports, auto_config_url, excludes)?;
 
         #[cfg(target_os = "linux")]
         crate::linux::network::set_system_proxy(enabled, &cfg)?;
@@ -48,3 +127,770 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeSetSyst
         Ok(())
     });
 }
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeSetProxyEnvironment(
+    env: *mut JNIEnv,
+    _: jclass,
+    enabled: jboolean,
+    app_id: jstring,
+    hosts: jobjectArray,
+    ports: jintArray,
+    excludes: jobjectArray,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (enabled, app_id, hosts, ports, excludes);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let enabled = enabled != JNI_FALSE;
+        let app_id = java_string_to_string(env, app_id);
+        let cfg = read_system_proxy_config(env, hosts, ports, null_mut(), excludes)?;
+
+        crate::linux::network::set_proxy_environment(enabled, &app_id, &cfg)?;
+
+        Ok(())
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeSetLinkDns(
+    env: *mut JNIEnv,
+    _: jclass,
+    enabled: jboolean,
+    interface_index: jint,
+    servers: jobjectArray,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (enabled, interface_index, servers);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let servers = iterate_object_array(env, servers)
+            .map(|s| java_string_to_string(env, s).parse::<std::net::IpAddr>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        crate::linux::network::set_link_dns(enabled != JNI_FALSE, interface_index as u32, &servers)
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeCreateTun(
+    env: *mut JNIEnv,
+    _: jclass,
+    name: jstring,
+    mtu: jint,
+    addresses: jobjectArray,
+    fds: jobjectArray,
+) -> jstring {
+    #[cfg(windows)]
+    return {
+        let _ = (name, mtu, addresses, fds);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let fds = iterate_object_array(env, fds).collect::<Vec<_>>();
+        let cfg = crate::common::network::TunConfig {
+            name: java_string_to_string(env, name),
+            mtu: u32::try_from(mtu).map_err(|_| format!("invalid mtu: {mtu}"))?,
+            addresses: iterate_object_array(env, addresses)
+                .map(|s| java_string_to_string(env, s))
+                .collect::<Vec<_>>(),
+            queues: fds.len(),
+        };
+
+        let (name, tun_fds) = crate::linux::tun::create_tun(&cfg)?;
+
+        for (fd, value) in fds.into_iter().zip(tun_fds) {
+            crate::file::set_file_descriptor(env, fd, value, false);
+        }
+
+        Ok(string_to_java_string(env, &name))
+    })
+    .unwrap_or(null_mut());
+}
+
+static C_NATIVE_SYSTEM_PROXY: LazyJRef<jclass> =
+    LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativeSystemProxy"));
+static M_NEW_NATIVE_SYSTEM_PROXY: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_NATIVE_SYSTEM_PROXY,
+        "<init>",
+        "(I[Ljava/lang/String;[ILjava/lang/String;[Ljava/lang/String;)V",
+    ))
+});
+
+fn new_native_system_proxy(env: *mut JNIEnv, settings: &SystemProxySettings) -> jobject {
+    let mode = match settings.mode {
+        ProxyMode::None => 0,
+        ProxyMode::Manual => 1,
+        ProxyMode::Auto => 2,
+    };
+
+    let endpoints = [&settings.http, &settings.https, &settings.ftp, &settings.socks];
+    let hosts = endpoints
+        .iter()
+        .map(|e| e.as_ref().map(|e| e.host.as_str()).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let ports = endpoints
+        .iter()
+        .map(|e| e.as_ref().map(|e| e.port as jint).unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let auto_config_url = match &settings.auto_config_url {
+        Some(url) => string_to_java_string(env, url),
+        None => null_mut(),
+    };
+
+    jcall!(
+        env,
+        NewObject,
+        *C_NATIVE_SYSTEM_PROXY.get(),
+        *M_NEW_NATIVE_SYSTEM_PROXY.get(),
+        mode as jint,
+        new_java_string_array(env, &hosts),
+        new_java_int_array(env, &ports),
+        auto_config_url,
+        new_java_string_array(env, &settings.excludes)
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeGetSystemProxy(env: *mut JNIEnv, _: jclass) -> jobject {
+    rethrow_java_io_exception(env, || {
+        #[cfg(target_os = "linux")]
+        let settings = crate::linux::network::get_system_proxy()?;
+
+        #[cfg(windows)]
+        let settings = crate::win32::network::get_system_proxy()?;
+
+        Ok(new_native_system_proxy(env, &settings))
+    })
+    .unwrap_or(null_mut())
+}
+
+#[cfg(target_os = "linux")]
+static M_NATIVE_PROXY_CHANGED_LISTENER_ON_CHANGED: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        "com/github/kr328/clash/compat/NetworkCompat$NativeProxyChangedListener",
+        "onChanged",
+        "(Lcom/github/kr328/clash/compat/NetworkCompat$NativeSystemProxy;)V",
+    ))
+});
+
+#[cfg(target_os = "linux")]
+struct ListenerImpl {
+    listener_ref: crate::helper::refs::GlobalRef,
+}
+
+#[cfg(target_os = "linux")]
+impl crate::common::network::Listener for ListenerImpl {
+    fn on_changed(&self, settings: &SystemProxySettings) {
+        let env = crate::helper::vm::attach_current_thread();
+
+        jcall!(
+            *env,
+            CallVoidMethod,
+            *self.listener_ref,
+            *M_NATIVE_PROXY_CHANGED_LISTENER_ON_CHANGED.get(),
+            new_native_system_proxy(*env, settings)
+        );
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeAddProxyChangedListener(
+    env: *mut JNIEnv,
+    _: jclass,
+    hosts: jobjectArray,
+    ports: jintArray,
+    auto_config_url: jstring,
+    excludes: jobjectArray,
+    listener: jobject,
+) -> jlong {
+    #[cfg(windows)]
+    return {
+        let _ = (hosts, ports, auto_config_url, excludes, listener);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        -1
+    };
+
+    #[cfg(target_os = "linux")]
+    return {
+        let listener_ref = crate::helper::refs::GlobalRef::new(listener);
+
+        rethrow_java_io_exception(env, move || {
+            let enforce = if hosts.is_null() {
+                None
+            } else {
+                Some(read_system_proxy_config(env, hosts, ports, auto_config_url, excludes)?)
+            };
+
+            let holder = crate::linux::network::add_proxy_changed_listener(enforce, ListenerImpl { listener_ref })?;
+
+            Ok(Box::into_raw(Box::new(holder)) as jlong)
+        })
+        .unwrap_or(-1)
+    };
+}
+
+#[cfg(target_os = "linux")]
+static M_ON_NETWORK_CHANGED_LISTENER_ON_CHANGED: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        "com/github/kr328/clash/compat/NetworkCompat$OnNetworkChangedListener",
+        "onChanged",
+        "(I)V",
+    ))
+});
+
+#[cfg(target_os = "linux")]
+struct ChangeListenerImpl {
+    listener_ref: crate::helper::refs::GlobalRef,
+}
+
+#[cfg(target_os = "linux")]
+impl crate::common::network::ChangeListener for ChangeListenerImpl {
+    fn on_network_changed(&self, changes: u32) {
+        let env = crate::helper::vm::attach_current_thread();
+
+        jcall!(
+            *env,
+            CallVoidMethod,
+            *self.listener_ref,
+            *M_ON_NETWORK_CHANGED_LISTENER_ON_CHANGED.get(),
+            changes as jint
+        );
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeAddNetworkChangedListener(
+    env: *mut JNIEnv,
+    _: jclass,
+    listener: jobject,
+) -> jlong {
+    #[cfg(windows)]
+    return {
+        let _ = listener;
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        -1
+    };
+
+    #[cfg(target_os = "linux")]
+    return {
+        let listener_ref = crate::helper::refs::GlobalRef::new(listener);
+
+        rethrow_java_io_exception(env, move || {
+            let holder = crate::linux::netlink::monitor::add_network_changed_listener(ChangeListenerImpl { listener_ref })?;
+
+            Ok(Box::into_raw(Box::new(holder)) as jlong)
+        })
+        .unwrap_or(-1)
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeReleaseHolder(_: *mut JNIEnv, _: jclass, ptr: jlong) {
+    unsafe { drop(Box::from_raw(ptr as *mut Box<dyn Holder>)) }
+}
+
+static C_NATIVE_INTERFACE: LazyJRef<jclass> =
+    LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativeInterface"));
+static M_NEW_NATIVE_INTERFACE: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_NATIVE_INTERFACE,
+        "<init>",
+        "(Ljava/lang/String;IIILjava/lang/String;II[B[Ljava/lang/String;)V",
+    ))
+});
+
+fn new_native_interface(env: *mut JNIEnv, interface: &NetworkInterface) -> jobject {
+    let oper_state = match interface.oper_state {
+        OperState::Unknown => 0,
+        OperState::NotPresent => 1,
+        OperState::Down => 2,
+        OperState::LowerLayerDown => 3,
+        OperState::Testing => 4,
+        OperState::Dormant => 5,
+        OperState::Up => 6,
+    };
+
+    let kind = match &interface.kind {
+        Some(kind) => string_to_java_string(env, kind),
+        None => null_mut(),
+    };
+
+    let hardware_address = match &interface.hardware_address {
+        Some(address) => new_java_byte_array(env, address),
+        None => null_mut(),
+    };
+
+    let addresses = interface
+        .addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<_>>();
+
+    jcall!(
+        env,
+        NewObject,
+        *C_NATIVE_INTERFACE.get(),
+        *M_NEW_NATIVE_INTERFACE.get(),
+        string_to_java_string(env, &interface.name),
+        interface.index as jint,
+        interface.flags as jint,
+        interface.link_type as jint,
+        kind,
+        oper_state as jint,
+        interface.mtu as jint,
+        hardware_address,
+        new_java_string_array(env, &addresses)
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeListInterfaces(
+    env: *mut JNIEnv,
+    _: jclass,
+) -> jobjectArray {
+    #[cfg(windows)]
+    return {
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let interfaces = crate::linux::netlink::link::list_interfaces()?
+            .iter()
+            .map(|interface| new_native_interface(env, interface))
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_NATIVE_INTERFACE.get(), &interfaces))
+    })
+    .unwrap_or(null_mut());
+}
+
+static C_NATIVE_ROUTE: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativeRoute"));
+static M_NEW_NATIVE_ROUTE: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        &C_NATIVE_ROUTE,
+        "<init>",
+        "(Ljava/lang/String;Ljava/lang/String;ILjava/lang/String;II)V",
+    ))
+});
+
+fn new_native_route(env: *mut JNIEnv, route: &Route) -> jobject {
+    let gateway = match &route.gateway {
+        Some(gateway) => string_to_java_string(env, &gateway.to_string()),
+        None => null_mut(),
+    };
+
+    jcall!(
+        env,
+        NewObject,
+        *C_NATIVE_ROUTE.get(),
+        *M_NEW_NATIVE_ROUTE.get(),
+        string_to_java_string(env, &route.destination.to_string()),
+        gateway,
+        route.interface_index as jint,
+        string_to_java_string(env, &route.interface_name),
+        route.metric as jint,
+        route.table as jint
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeGetDefaultRoutes(
+    env: *mut JNIEnv,
+    _: jclass,
+) -> jobjectArray {
+    #[cfg(windows)]
+    return {
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let routes = crate::linux::netlink::route::list_default_routes()?
+            .iter()
+            .map(|route| new_native_route(env, route))
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_NATIVE_ROUTE.get(), &routes))
+    })
+    .unwrap_or(null_mut());
+}
+
+fn read_route(
+    env: *mut JNIEnv,
+    destination: jstring,
+    gateway: jstring,
+    interface_name: jstring,
+    metric: jint,
+    table: jint,
+) -> Result<Route, Box<dyn Error>> {
+    Ok(Route {
+        destination: java_string_to_string(env, destination).parse::<InterfaceAddress>()?,
+        gateway: if gateway.is_null() {
+            None
+        } else {
+            Some(java_string_to_string(env, gateway).parse()?)
+        },
+        interface_index: 0,
+        interface_name: java_string_to_string(env, interface_name),
+        metric: metric as u32,
+        table: table as u32,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeAddRoute(
+    env: *mut JNIEnv,
+    _: jclass,
+    destination: jstring,
+    gateway: jstring,
+    interface_name: jstring,
+    metric: jint,
+    table: jint,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (destination, gateway, interface_name, metric, table);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let route = read_route(env, destination, gateway, interface_name, metric, table)?;
+
+        crate::linux::netlink::route::add_route(&route)
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeDeleteRoute(
+    env: *mut JNIEnv,
+    _: jclass,
+    destination: jstring,
+    gateway: jstring,
+    interface_name: jstring,
+    metric: jint,
+    table: jint,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (destination, gateway, interface_name, metric, table);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        let route = read_route(env, destination, gateway, interface_name, metric, table)?;
+
+        crate::linux::netlink::route::delete_route(&route)
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeListInstalledRoutes(
+    env: *mut JNIEnv,
+    _: jclass,
+) -> jobjectArray {
+    #[cfg(windows)]
+    return {
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let routes = crate::linux::netlink::route::list_installed_routes()?
+            .iter()
+            .map(|route| new_native_route(env, route))
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_NATIVE_ROUTE.get(), &routes))
+    })
+    .unwrap_or(null_mut());
+}
+
+static C_RULE: LazyJRef<jclass> = LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$Rule"));
+static M_NEW_RULE: LazyJRef<jmethodID> = LazyJRef::new(|| JRef::from((&C_RULE, "<init>", "(ZIII)V")));
+
+fn read_rule(ipv6: jboolean, fwmark: jint, priority: jint, table: jint) -> Rule {
+    Rule {
+        ipv6: ipv6 != JNI_FALSE,
+        fwmark: fwmark as u32,
+        priority: priority as u32,
+        table: table as u32,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeAddRule(
+    env: *mut JNIEnv,
+    _: jclass,
+    ipv6: jboolean,
+    fwmark: jint,
+    priority: jint,
+    table: jint,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (ipv6, fwmark, priority, table);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        crate::linux::netlink::rule::add_rule(&read_rule(ipv6, fwmark, priority, table))
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeDeleteRule(
+    env: *mut JNIEnv,
+    _: jclass,
+    ipv6: jboolean,
+    fwmark: jint,
+    priority: jint,
+    table: jint,
+) {
+    #[cfg(windows)]
+    {
+        let _ = (ipv6, fwmark, priority, table);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+    }
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        crate::linux::netlink::rule::delete_rule(&read_rule(ipv6, fwmark, priority, table))
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeListInstalledRules(
+    env: *mut JNIEnv,
+    _: jclass,
+) -> jobjectArray {
+    #[cfg(windows)]
+    return {
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let rules = crate::linux::netlink::rule::list_installed_rules()?
+            .iter()
+            .map(|rule| {
+                jcall!(
+                    env,
+                    NewObject,
+                    *C_RULE.get(),
+                    *M_NEW_RULE.get(),
+                    rule.ipv6 as jint,
+                    rule.fwmark as jint,
+                    rule.priority as jint,
+                    rule.table as jint
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_RULE.get(), &rules))
+    })
+    .unwrap_or(null_mut());
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeClearInstalledRoutingPolicy(
+    env: *mut JNIEnv,
+    _: jclass,
+) {
+    #[cfg(windows)]
+    crate::helper::throwable::throw_unsupported_operation(env);
+
+    #[cfg(target_os = "linux")]
+    rethrow_java_io_exception(env, || {
+        crate::linux::netlink::rule::flush_installed_rules()?;
+        crate::linux::netlink::route::flush_installed_routes()
+    });
+}
+
+static C_NATIVE_PORT_OWNER: LazyJRef<jclass> =
+    LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativePortOwner"));
+static M_NEW_NATIVE_PORT_OWNER: LazyJRef<jmethodID> =
+    LazyJRef::new(|| JRef::from((&C_NATIVE_PORT_OWNER, "<init>", "(ILjava/lang/String;[Ljava/lang/String;)V")));
+
+fn new_native_port_owner(env: *mut JNIEnv, owner: &PortOwner) -> jobject {
+    let executable = match &owner.executable {
+        Some(executable) => string_to_java_string(env, executable),
+        None => null_mut(),
+    };
+
+    jcall!(
+        env,
+        NewObject,
+        *C_NATIVE_PORT_OWNER.get(),
+        *M_NEW_NATIVE_PORT_OWNER.get(),
+        owner.pid as jint,
+        executable,
+        new_java_string_array(env, &owner.command_line)
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeFindPortOwners(
+    env: *mut JNIEnv,
+    _: jclass,
+    udp: jboolean,
+    port: jint,
+) -> jobjectArray {
+    #[cfg(windows)]
+    return {
+        let _ = (udp, port);
+
+        crate::helper::throwable::throw_unsupported_operation(env);
+
+        null_mut()
+    };
+
+    #[cfg(target_os = "linux")]
+    return rethrow_java_io_exception(env, || {
+        let protocol = if udp != JNI_FALSE {
+            crate::common::network::Protocol::Udp
+        } else {
+            crate::common::network::Protocol::Tcp
+        };
+        let port = u16::try_from(port).map_err(|_| format!("invalid port: {port}"))?;
+
+        let owners = crate::linux::port::find_port_owners(protocol, port)?
+            .iter()
+            .map(|owner| new_native_port_owner(env, owner))
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_NATIVE_PORT_OWNER.get(), &owners))
+    })
+    .unwrap_or(null_mut());
+}
+
+const BIND_TCP: jint = 1;
+const BIND_UDP: jint = 1 << 1;
+const BIND_REUSE_ADDRESS: jint = 1 << 2;
+
+static C_NATIVE_BIND_RESULT: LazyJRef<jclass> =
+    LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativeBindResult"));
+static M_NEW_NATIVE_BIND_RESULT: LazyJRef<jmethodID> =
+    LazyJRef::new(|| JRef::from((&C_NATIVE_BIND_RESULT, "<init>", "(Ljava/lang/String;I)V")));
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeProbeBind(
+    env: *mut JNIEnv,
+    _: jclass,
+    hosts: jobjectArray,
+    ports: jintArray,
+    flags: jintArray,
+    range_start: jint,
+    range_end: jint,
+) -> jobjectArray {
+    rethrow_java_io_exception(env, || {
+        let requests = iterate_object_array(env, hosts)
+            .map(|host| java_string_to_string(env, host))
+            .zip(collect_java_ints(env, ports))
+            .zip(collect_java_ints(env, flags))
+            .map(|((host, port), flags)| {
+                Ok(BindRequest {
+                    address: SocketAddr::new(host.parse()?, u16::try_from(port)?),
+                    tcp: flags & BIND_TCP != 0,
+                    udp: flags & BIND_UDP != 0,
+                    reuse_address: flags & BIND_REUSE_ADDRESS != 0,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        let range_start = u16::try_from(range_start)?;
+        let range_end = u16::try_from(range_end)?;
+
+        let results = crate::utils::bind::probe_ports(&requests, range_start..=range_end)
+            .iter()
+            .map(|result| {
+                let error = match &result.error {
+                    Some(error) => string_to_java_string(env, error),
+                    None => null_mut(),
+                };
+
+                jcall!(
+                    env,
+                    NewObject,
+                    *C_NATIVE_BIND_RESULT.get(),
+                    *M_NEW_NATIVE_BIND_RESULT.get(),
+                    error,
+                    result.suggestion.map(|port| port as jint).unwrap_or(-1)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Ok(new_java_object_array(env, *C_NATIVE_BIND_RESULT.get(), &results))
+    })
+    .unwrap_or(null_mut())
+}
+
+static C_NATIVE_EXCLUDE_RESULT: LazyJRef<jclass> =
+    LazyJRef::new(|| JRef::from("com/github/kr328/clash/compat/NetworkCompat$NativeExcludeResult"));
+static M_NEW_NATIVE_EXCLUDE_RESULT: LazyJRef<jmethodID> =
+    LazyJRef::new(|| JRef::from((&C_NATIVE_EXCLUDE_RESULT, "<init>", "(Ljava/lang/String;Ljava/lang/String;)V")));
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_NetworkCompat_nativeValidateExcludes(
+    env: *mut JNIEnv,
+    _: jclass,
+    excludes: jobjectArray,
+) -> jobjectArray {
+    let results = iterate_object_array(env, excludes)
+        .map(|s| java_string_to_string(env, s))
+        .map(|s| {
+            let (normalized, error) = match s.parse::<ProxyExclude>() {
+                Ok(exclude) => (string_to_java_string(env, &exclude.to_string()), null_mut()),
+                Err(err) => (null_mut(), string_to_java_string(env, &err.to_string())),
+            };
+
+            jcall!(
+                env,
+                NewObject,
+                *C_NATIVE_EXCLUDE_RESULT.get(),
+                *M_NEW_NATIVE_EXCLUDE_RESULT.get(),
+                normalized,
+                error
+            )
+        })
+        .collect::<Vec<_>>();
+
+    new_java_object_array(env, *C_NATIVE_EXCLUDE_RESULT.get(), &results)
+}
@@ -28,21 +28,10 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_SecurityCompat_nativeGetSEL
     .unwrap_or(null_mut())
 }
 
-#[cfg(windows)]
-fn throw_unsupported(env: *mut JNIEnv) {
-    let clazz = crate::helper::call::jcall!(
-        env,
-        FindClass,
-        cstr::cstr!("java/lang/UnsupportedOperationException").as_ptr()
-    );
-
-    crate::helper::call::jcall!(env, ThrowNew, clazz, cstr::cstr!("Unsupported platform").as_ptr());
-}
-
 #[no_mangle]
 #[cfg(windows)]
 pub extern "C" fn Java_com_github_kr328_clash_compat_SecurityCompat_nativeGetUnixUid(env: *mut JNIEnv, _: jclass) -> jint {
-    throw_unsupported(env);
+    crate::helper::throwable::throw_unsupported_operation(env);
 
     -1
 }
@@ -50,7 +39,7 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_SecurityCompat_nativeGetUni
 #[no_mangle]
 #[cfg(windows)]
 pub extern "C" fn Java_com_github_kr328_clash_compat_SecurityCompat_nativeGetUnixGid(env: *mut JNIEnv, _: jclass) -> jint {
-    throw_unsupported(env);
+    crate::helper::throwable::throw_unsupported_operation(env);
 
     -1
 }
@@ -61,7 +50,7 @@ pub extern "C" fn Java_com_github_kr328_clash_compat_SecurityCompat_nativeGetSEL
     env: *mut JNIEnv,
     _: jclass,
 ) -> jstring {
-    throw_unsupported(env);
+    crate::helper::throwable::throw_unsupported_operation(env);
 
     null_mut()
 }
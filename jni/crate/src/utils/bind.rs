@@ -0,0 +1,152 @@
+use std::{collections::HashSet, io, net::SocketAddr, ops::RangeInclusive};
+
+use socket2::{Domain, Socket, Type};
+
+use crate::common::network::{BindRequest, BindResult};
+
+// Mirrors net.Listen of Go, which always sets SO_REUSEADDR on listeners outside windows so ports in TIME_WAIT
+// stay usable, a bound stream socket is only in use once it listens
+fn bind_tcp(address: SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, None)?;
+
+    socket.set_reuse_address(cfg!(unix))?;
+    socket.bind(&address.into())?;
+    socket.listen(1)?;
+
+    Ok(socket)
+}
+
+// reuse_address only affects datagram sockets, for sharing the port the way multicast listeners do
+fn bind_udp(address: SocketAddr, reuse_address: bool) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(address), Type::DGRAM, None)?;
+
+    socket.set_reuse_address(reuse_address)?;
+    socket.bind(&address.into())?;
+
+    Ok(socket)
+}
+
+pub fn probe_bind(request: &BindRequest) -> io::Result<()> {
+    // Hold all sockets until every protocol is bound, port must be free for both at the same time
+    let mut sockets = Vec::with_capacity(2);
+
+    if request.tcp {
+        sockets.push(bind_tcp(request.address)?);
+    }
+    if request.udp {
+        sockets.push(bind_udp(request.address, request.reuse_address)?);
+    }
+
+    Ok(())
+}
+
+fn find_free_port(request: &BindRequest, range: RangeInclusive<u16>, excluded: &HashSet<u16>) -> Option<u16> {
+    range.filter(|port| *port != 0 && !excluded.contains(port)).find(|port| {
+        let mut candidate = request.clone();
+        candidate.address.set_port(*port);
+
+        probe_bind(&candidate).is_ok()
+    })
+}
+
+pub fn probe_ports(requests: &[BindRequest], range: RangeInclusive<u16>) -> Vec<BindResult> {
+    let mut excluded = requests.iter().map(|r| r.address.port()).collect::<HashSet<_>>();
+
+    requests
+        .iter()
+        .map(|request| match probe_bind(request) {
+            Ok(_) => BindResult {
+                error: None,
+                suggestion: None,
+            },
+            Err(err) => {
+                let suggestion = find_free_port(request, range.clone(), &excluded);
+                if let Some(port) = suggestion {
+                    excluded.insert(port);
+                }
+
+                BindResult {
+                    error: Some(err.to_string()),
+                    suggestion,
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        io::Read,
+        net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    };
+
+    use crate::{
+        common::network::BindRequest,
+        utils::bind::{probe_bind, probe_ports},
+    };
+
+    fn request(address: SocketAddr, tcp: bool, udp: bool) -> BindRequest {
+        BindRequest {
+            address,
+            tcp,
+            udp,
+            reuse_address: false,
+        }
+    }
+
+    #[test]
+    pub fn test_probe_bind() -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        assert!(probe_bind(&request(address, true, false)).is_err());
+        assert!(probe_bind(&request(address, false, true)).is_ok());
+
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        assert!(probe_bind(&request(socket.local_addr()?, false, true)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_probe_bind_time_wait() -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        // Closing the accepted side first leaves the port of the listener in TIME_WAIT
+        let mut client = TcpStream::connect(address)?;
+        let (accepted, _) = listener.accept()?;
+        drop(accepted);
+        client.read_to_end(&mut Vec::new())?;
+        drop(client);
+        drop(listener);
+
+        assert!(probe_bind(&request(address, true, false)).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_probe_ports() -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let start = address.port().saturating_sub(16);
+        let end = address.port().saturating_add(16);
+
+        let requests = [request(address, true, true), request(address, true, true)];
+        let results = probe_ports(&requests, start..=end);
+
+        assert!(results.iter().all(|r| r.error.is_some()));
+
+        let suggestions = results.iter().filter_map(|r| r.suggestion).collect::<Vec<_>>();
+        assert_eq!(suggestions.len(), 2);
+        assert_ne!(suggestions[0], suggestions[1]);
+        assert!(suggestions
+            .iter()
+            .all(|port| (start..=end).contains(port) && *port != address.port()));
+
+        Ok(())
+    }
+}
@@ -1 +1,2 @@
+pub mod bind;
 pub mod scoped;
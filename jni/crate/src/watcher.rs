@@ -0,0 +1,103 @@
+use jni_sys::{jboolean, jclass, jint, jlong, jmethodID, jobject, jobjectArray, jstring, JNIEnv, JNI_TRUE};
+
+use crate::{
+    common::watcher::{Holder, Listener, Watch},
+    helper::{
+        array::{iterate_object_array, new_java_byte_array},
+        call::jcall,
+        lazy::{JRef, LazyJRef},
+        refs::GlobalRef,
+        strings::java_string_to_string,
+        throwable::rethrow_java_io_exception,
+        vm::attach_current_thread,
+    },
+};
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_WatcherCompat_nativeIsSupported(_: *mut JNIEnv, _: jclass) -> jboolean {
+    #[cfg(windows)]
+    return jni_sys::JNI_FALSE;
+
+    #[cfg(target_os = "linux")]
+    return JNI_TRUE;
+}
+
+static M_NATIVE_WATCH_PATH: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        "com/github/kr328/clash/compat/WatcherCompat$NativeWatch",
+        "path",
+        "()Ljava/lang/String;",
+    ))
+});
+
+static M_NATIVE_WATCH_EVENTS: LazyJRef<jmethodID> =
+    LazyJRef::new(|| JRef::from(("com/github/kr328/clash/compat/WatcherCompat$NativeWatch", "events", "()I")));
+
+static M_NATIVE_LISTENER_ON_CHANGED: LazyJRef<jmethodID> = LazyJRef::new(|| {
+    JRef::from((
+        "com/github/kr328/clash/compat/WatcherCompat$NativeListener",
+        "onChanged",
+        "([BI)V",
+    ))
+});
+
+struct ListenerImpl {
+    listener_ref: GlobalRef,
+}
+
+impl Listener for ListenerImpl {
+    fn on_changed(&self, path: &[u8], events: u32) {
+        let env = attach_current_thread();
+
+        jcall!(
+            *env,
+            CallVoidMethod,
+            *self.listener_ref,
+            *M_NATIVE_LISTENER_ON_CHANGED.get(),
+            new_java_byte_array(*env, path),
+            events as jint
+        );
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_WatcherCompat_nativeAddWatcher(
+    env: *mut JNIEnv,
+    _: jclass,
+    watches: jobjectArray,
+    listener: jobject,
+) -> jlong {
+    rethrow_java_io_exception(env, || {
+        let watches = iterate_object_array(env, watches)
+            .map(|watch| {
+                let path = jcall!(env, CallObjectMethod, watch, *M_NATIVE_WATCH_PATH.get()) as jstring;
+                let events = jcall!(env, CallIntMethod, watch, *M_NATIVE_WATCH_EVENTS.get());
+
+                Watch {
+                    path: java_string_to_string(env, path),
+                    events: events as u32,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let listener = ListenerImpl {
+            listener_ref: GlobalRef::new(listener),
+        };
+
+        #[cfg(windows)]
+        return {
+            let _ = (watches, listener);
+
+            Err("unsupported".into())
+        };
+
+        #[cfg(target_os = "linux")]
+        return Ok(Box::into_raw(Box::new(crate::linux::watcher::add_watcher(&watches, listener)?)) as jlong);
+    })
+    .unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C" fn Java_com_github_kr328_clash_compat_WatcherCompat_nativeReleaseWatcher(_: *mut JNIEnv, _: jclass, token: jlong) {
+    unsafe { drop(Box::from_raw(token as *mut Box<dyn Holder>)) }
+}
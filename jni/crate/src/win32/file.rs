@@ -2,11 +2,13 @@ use std::{env::temp_dir, mem::size_of};
 
 use rand::{thread_rng, Rng};
 use windows::{
+    core::PSTR,
     Win32,
     Win32::{
         Foundation::{CloseHandle, SetHandleInformation, FALSE, HANDLE, HANDLE_FLAGS, HANDLE_FLAG_INHERIT, INVALID_HANDLE_VALUE},
         Networking::WinSock::{
-            accept, bind, connect, listen, WSASocketW, ADDRESS_FAMILY, AF_UNIX, INVALID_SOCKET, SOCKADDR_UN, SOCKET, SOCK_STREAM,
+            accept, bind, connect, getsockopt, listen, WSASocketW, ADDRESS_FAMILY, AF_INET, AF_INET6, AF_UNIX, INVALID_SOCKET,
+            SOCKADDR_UN, SOCKET, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET, SO_ACCEPTCONN, SO_PROTOCOL_INFOW, WSAPROTOCOL_INFOW,
             WSA_FLAG_OVERLAPPED,
         },
         System::Pipes::CreatePipe,
@@ -15,7 +17,11 @@ use windows::{
 
 use error::Error;
 
-use crate::{common::file::FileDescriptor, utils::scoped::Scoped, win32::error};
+use crate::{
+    common::file::{FileDescriptor, SocketFamily, SocketInfo, SocketType},
+    utils::scoped::Scoped,
+    win32::error,
+};
 
 pub fn set_file_descriptor_inheritable(fd: FileDescriptor, inheritable: bool) -> Result<(), Box<dyn std::error::Error>> {
     unsafe {
@@ -94,6 +100,56 @@ pub fn create_socket_pair() -> Result<(FileDescriptor, FileDescriptor), Box<dyn
     ))
 }
 
+pub fn get_socket_info(fd: FileDescriptor) -> Result<SocketInfo, Box<dyn std::error::Error>> {
+    let mut info = WSAPROTOCOL_INFOW::default();
+    let mut listening: i32 = 0;
+
+    unsafe {
+        let mut length = size_of::<WSAPROTOCOL_INFOW>() as i32;
+        if getsockopt(
+            SOCKET(fd as usize),
+            SOL_SOCKET,
+            SO_PROTOCOL_INFOW,
+            PSTR((&mut info as *mut WSAPROTOCOL_INFOW).cast()),
+            &mut length,
+        ) != 0
+        {
+            return Err(Error::with_current("getsockopt").into());
+        }
+
+        let mut length = size_of::<i32>() as i32;
+        if getsockopt(
+            SOCKET(fd as usize),
+            SOL_SOCKET,
+            SO_ACCEPTCONN,
+            PSTR((&mut listening as *mut i32).cast()),
+            &mut length,
+        ) != 0
+        {
+            return Err(Error::with_current("getsockopt").into());
+        }
+    }
+
+    let family = match info.iAddressFamily {
+        family if family == AF_INET.0 as i32 => SocketFamily::Inet,
+        family if family == AF_INET6.0 as i32 => SocketFamily::Inet6,
+        family if family == AF_UNIX as i32 => SocketFamily::Unix,
+        family => return Err(format!("unsupported socket family: {family}").into()),
+    };
+
+    let socket_type = match info.iSocketType {
+        socket_type if socket_type == SOCK_STREAM.0 => SocketType::Stream,
+        socket_type if socket_type == SOCK_DGRAM.0 => SocketType::Datagram,
+        socket_type => return Err(format!("unsupported socket type: {socket_type}").into()),
+    };
+
+    Ok(SocketInfo {
+        family,
+        socket_type,
+        listening: listening != 0,
+    })
+}
+
 fn close_handle(handle: HANDLE) {
     unsafe {
         CloseHandle(handle);
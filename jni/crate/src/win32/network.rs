@@ -1,32 +1,46 @@
-use crate::{common::network::SystemProxyConfig, win32::strings::Win32StringIntoExt};
-use std::{error::Error, ffi::c_void, io, mem::size_of, ptr::null};
+use crate::{
+    common::network::{ProxyEndpoint, ProxyExclude, ProxyMode, SystemProxyConfig, SystemProxySettings},
+    utils::scoped::Scoped,
+    win32::strings::{Win32StringFromExt, Win32StringIntoExt},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::c_void,
+    io,
+    mem::size_of,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    ptr::null,
+};
 use windows::{
-    core::PWSTR,
+    core::{PCWSTR, PWSTR},
+    w,
     Win32::{
         Foundation::FALSE,
         Networking::WinInet::{
-            InternetSetOptionW, INTERNET_OPTION_PER_CONNECTION_OPTION, INTERNET_PER_CONN, INTERNET_PER_CONN_FLAGS,
-            INTERNET_PER_CONN_FLAGS_UI, INTERNET_PER_CONN_OPTIONW, INTERNET_PER_CONN_OPTIONW_0, INTERNET_PER_CONN_OPTION_LISTW,
-            INTERNET_PER_CONN_PROXY_BYPASS, INTERNET_PER_CONN_PROXY_SERVER, PROXY_TYPE_DIRECT, PROXY_TYPE_PROXY,
+            InternetSetOptionW, INTERNET_OPTION_PER_CONNECTION_OPTION, INTERNET_PER_CONN, INTERNET_PER_CONN_AUTOCONFIG_URL,
+            INTERNET_PER_CONN_FLAGS, INTERNET_PER_CONN_FLAGS_UI, INTERNET_PER_CONN_OPTIONW, INTERNET_PER_CONN_OPTIONW_0,
+            INTERNET_PER_CONN_OPTION_LISTW, INTERNET_PER_CONN_PROXY_BYPASS, INTERNET_PER_CONN_PROXY_SERVER,
+            PROXY_TYPE_AUTO_PROXY_URL, PROXY_TYPE_DIRECT, PROXY_TYPE_PROXY,
+        },
+        System::Registry::{
+            RegCloseKey, RegOpenKeyExW, RegQueryValueExW, HKEY, HKEY_CURRENT_USER, KEY_QUERY_VALUE, REG_DWORD, REG_SZ,
+            REG_VALUE_TYPE,
         },
     },
 };
 
-pub fn set_system_proxy(enabled: bool, cfg: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+fn apply_proxy_options(
+    flags: u32,
+    server: Option<&str>,
+    bypass: Option<&str>,
+    auto_config_url: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
     unsafe {
-        let flags: u32;
-        let address: Option<Vec<u16>>;
-        let bypass: Option<Vec<u16>>;
-
-        if enabled {
-            flags = PROXY_TYPE_DIRECT | PROXY_TYPE_PROXY;
-            address = Some(cfg.address.to_win32_utf16());
-            bypass = Some(cfg.excludes.join(";").to_win32_utf16());
-        } else {
-            flags = PROXY_TYPE_DIRECT;
-            address = None;
-            bypass = None;
-        }
+        let server = server.map(|s| s.to_win32_utf16());
+        let bypass = bypass.map(|s| s.to_win32_utf16());
+        let auto_config_url = auto_config_url.map(|s| s.to_win32_utf16());
 
         fn to_pwstr_or_null(s: &Option<Vec<u16>>) -> PWSTR {
             PWSTR::from_raw(s.as_ref().map(|a| a.as_ptr()).unwrap_or(null()).cast_mut().cast())
@@ -40,7 +54,7 @@ pub fn set_system_proxy(enabled: bool, cfg: &SystemProxyConfig) -> Result<(), Bo
             INTERNET_PER_CONN_OPTIONW {
                 dwOption: INTERNET_PER_CONN_PROXY_SERVER,
                 Value: INTERNET_PER_CONN_OPTIONW_0 {
-                    pszValue: to_pwstr_or_null(&address),
+                    pszValue: to_pwstr_or_null(&server),
                 },
             },
             INTERNET_PER_CONN_OPTIONW {
@@ -49,6 +63,12 @@ pub fn set_system_proxy(enabled: bool, cfg: &SystemProxyConfig) -> Result<(), Bo
                     pszValue: to_pwstr_or_null(&bypass),
                 },
             },
+            INTERNET_PER_CONN_OPTIONW {
+                dwOption: INTERNET_PER_CONN_AUTOCONFIG_URL,
+                Value: INTERNET_PER_CONN_OPTIONW_0 {
+                    pszValue: to_pwstr_or_null(&auto_config_url),
+                },
+            },
             INTERNET_PER_CONN_OPTIONW {
                 dwOption: INTERNET_PER_CONN(INTERNET_PER_CONN_FLAGS_UI),
                 Value: INTERNET_PER_CONN_OPTIONW_0 { dwValue: flags },
@@ -76,3 +96,263 @@ pub fn set_system_proxy(enabled: bool, cfg: &SystemProxyConfig) -> Result<(), Bo
 
     Ok(())
 }
+
+fn snapshot_path() -> Result<PathBuf, Box<dyn Error>> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA").ok_or("LOCALAPPDATA not found")?;
+
+    Ok(PathBuf::from(local_app_data)
+        .join("clash-multiplatform-compat")
+        .join("system-proxy.snapshot"))
+}
+
+fn open_internet_settings() -> Result<Scoped<HKEY, fn(&HKEY)>, Box<dyn Error>> {
+    unsafe {
+        let mut key: Scoped<HKEY, fn(&HKEY)> = Scoped::new(Default::default(), |key| {
+            RegCloseKey(*key);
+        });
+
+        RegOpenKeyExW(
+            HKEY_CURRENT_USER,
+            w!("Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings"),
+            0,
+            KEY_QUERY_VALUE,
+            &mut *key,
+        )
+        .ok()?;
+
+        Ok(key)
+    }
+}
+
+// Snapshot file is `key=value` lines of the raw Internet Settings values
+fn save_snapshot(path: &Path) -> Result<(), Box<dyn Error>> {
+    let key = open_internet_settings()?;
+
+    let content = unsafe {
+        format!(
+            "ProxyEnable={}\nProxyServer={}\nProxyOverride={}\nAutoConfigURL={}\n",
+            query_registry_dword(*key, w!("ProxyEnable")).unwrap_or(0),
+            query_registry_string(*key, w!("ProxyServer")).unwrap_or_default(),
+            query_registry_string(*key, w!("ProxyOverride")).unwrap_or_default(),
+            query_registry_string(*key, w!("AutoConfigURL")).unwrap_or_default(),
+        )
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, content)?;
+    std::fs::rename(&temp, path)?;
+
+    Ok(())
+}
+
+fn restore_snapshot(content: &str) -> Result<(), Box<dyn Error>> {
+    let values: HashMap<&str, &str> = content.lines().filter_map(|line| line.split_once('=')).collect();
+    let value = |name: &str| values.get(name).copied().filter(|v| !v.is_empty());
+
+    let mut flags = PROXY_TYPE_DIRECT;
+    if value("ProxyEnable").unwrap_or("0") != "0" {
+        flags |= PROXY_TYPE_PROXY;
+    }
+    if value("AutoConfigURL").is_some() {
+        flags |= PROXY_TYPE_AUTO_PROXY_URL;
+    }
+
+    apply_proxy_options(flags, value("ProxyServer"), value("ProxyOverride"), value("AutoConfigURL"))
+}
+
+// ProxyOverride only knows `*` wildcards, ipv4 networks are expanded into patterns on whole octets
+fn format_excludes(excludes: &[ProxyExclude]) -> Result<String, Box<dyn Error>> {
+    let mut patterns = Vec::new();
+
+    for exclude in excludes {
+        match exclude {
+            ProxyExclude::Local => patterns.extend(["<local>".to_owned(), "127.*".to_owned(), "::1".to_owned()]),
+            ProxyExclude::Host(host) => patterns.push(host.clone()),
+            ProxyExclude::Wildcard(domain) => patterns.push(format!("*.{domain}")),
+            ProxyExclude::Network(network) => match network.address {
+                IpAddr::V4(_) if network.prefix_len == 32 => patterns.push(network.address.to_string()),
+                IpAddr::V4(_) if network.prefix_len == 0 => patterns.push("*".to_owned()),
+                IpAddr::V4(address) => {
+                    let octets = (network.prefix_len as u32 + 7) / 8;
+                    let base = u32::from(address);
+
+                    for index in 0..1u32 << (octets * 8 - network.prefix_len as u32) {
+                        let address = Ipv4Addr::from(base | index << (32 - octets * 8)).octets();
+                        let prefix = address[..octets as usize].iter().map(|o| o.to_string()).collect::<Vec<_>>();
+
+                        patterns.push(if octets == 4 {
+                            prefix.join(".")
+                        } else {
+                            format!("{}.*", prefix.join("."))
+                        });
+                    }
+                }
+                IpAddr::V6(_) if network.prefix_len == 128 => patterns.push(network.address.to_string()),
+                IpAddr::V6(_) => return Err(format!("ipv6 network excludes are not supported: {exclude}").into()),
+            },
+        }
+    }
+
+    Ok(patterns.join(";"))
+}
+
+pub fn set_system_proxy(enabled: bool, cfg: &SystemProxyConfig) -> Result<(), Box<dyn Error>> {
+    let path = snapshot_path()?;
+
+    if enabled {
+        // Keep the oldest snapshot, settings already overridden by us are not worth restoring
+        if !path.exists() {
+            save_snapshot(&path)?;
+        }
+
+        if let Some(url) = &cfg.auto_config_url {
+            return apply_proxy_options(
+                PROXY_TYPE_DIRECT | PROXY_TYPE_AUTO_PROXY_URL,
+                None,
+                Some(&format_excludes(&cfg.excludes)?),
+                Some(url),
+            );
+        }
+
+        let server = [
+            ("http", &cfg.http),
+            ("https", &cfg.https),
+            ("ftp", &cfg.ftp),
+            ("socks", &cfg.socks),
+        ]
+        .iter()
+        .filter_map(|(protocol, endpoint)| endpoint.as_ref().map(|endpoint| format!("{protocol}={endpoint}")))
+        .collect::<Vec<_>>()
+        .join(";");
+
+        return apply_proxy_options(
+            PROXY_TYPE_DIRECT | PROXY_TYPE_PROXY,
+            Some(&server),
+            Some(&format_excludes(&cfg.excludes)?),
+            None,
+        );
+    }
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => restore_snapshot(&content)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => apply_proxy_options(PROXY_TYPE_DIRECT, None, None, None)?,
+        Err(err) => return Err(err.into()),
+    }
+
+    match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+unsafe fn query_registry_value(key: HKEY, name: PCWSTR) -> Option<(REG_VALUE_TYPE, Vec<u8>)> {
+    let mut value_type = REG_VALUE_TYPE::default();
+    let mut length: u32 = 0;
+
+    RegQueryValueExW(key, name, None, Some(&mut value_type), None, Some(&mut length))
+        .ok()
+        .ok()?;
+
+    let mut data = vec![0u8; length as usize];
+    RegQueryValueExW(
+        key,
+        name,
+        None,
+        Some(&mut value_type),
+        Some(data.as_mut_ptr()),
+        Some(&mut length),
+    )
+    .ok()
+    .ok()?;
+
+    data.truncate(length as usize);
+
+    Some((value_type, data))
+}
+
+unsafe fn query_registry_string(key: HKEY, name: PCWSTR) -> Option<String> {
+    match query_registry_value(key, name)? {
+        (REG_SZ, data) => {
+            let chars = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+
+            String::from_win32_utf16(&chars).ok()
+        }
+        _ => None,
+    }
+}
+
+unsafe fn query_registry_dword(key: HKEY, name: PCWSTR) -> Option<u32> {
+    match query_registry_value(key, name)? {
+        (REG_DWORD, data) if data.len() >= 4 => Some(u32::from_le_bytes([data[0], data[1], data[2], data[3]])),
+        _ => None,
+    }
+}
+
+fn parse_proxy_endpoint(value: &str) -> Option<ProxyEndpoint> {
+    let value = value.trim();
+    let value = value.split_once("://").map(|(_, rest)| rest).unwrap_or(value);
+    let (host, port) = value.rsplit_once(':')?;
+
+    Some(ProxyEndpoint {
+        host: host.trim_start_matches('[').trim_end_matches(']').to_owned(),
+        port: port.parse::<u16>().ok()?,
+    })
+}
+
+pub fn get_system_proxy() -> Result<SystemProxySettings, Box<dyn Error>> {
+    let key = open_internet_settings()?;
+
+    unsafe {
+        let mut settings = SystemProxySettings::default();
+
+        let server = query_registry_string(*key, w!("ProxyServer")).unwrap_or_default();
+        if server.contains('=') {
+            for entry in server.split(';') {
+                if let Some((protocol, address)) = entry.split_once('=') {
+                    let endpoint = parse_proxy_endpoint(address);
+
+                    match protocol.trim() {
+                        "http" => settings.http = endpoint,
+                        "https" => settings.https = endpoint,
+                        "ftp" => settings.ftp = endpoint,
+                        "socks" => settings.socks = endpoint,
+                        _ => {}
+                    }
+                }
+            }
+        } else if let Some(endpoint) = parse_proxy_endpoint(&server) {
+            settings.http = Some(endpoint.clone());
+            settings.https = Some(endpoint.clone());
+            settings.ftp = Some(endpoint);
+        }
+
+        settings.auto_config_url = query_registry_string(*key, w!("AutoConfigURL")).filter(|url| !url.is_empty());
+        settings.excludes = query_registry_string(*key, w!("ProxyOverride"))
+            .map(|hosts| {
+                hosts
+                    .split(';')
+                    .map(|host| host.trim())
+                    .filter(|host| !host.is_empty())
+                    .map(|host| host.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        settings.mode = if settings.auto_config_url.is_some() {
+            ProxyMode::Auto
+        } else if query_registry_dword(*key, w!("ProxyEnable")).unwrap_or(0) != 0 {
+            ProxyMode::Manual
+        } else {
+            ProxyMode::None
+        };
+
+        Ok(settings)
+    }
+}